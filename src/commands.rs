@@ -41,7 +41,7 @@ fn add_tasks(
     crate::opts::Add { task }: &crate::opts::Add,
 ) -> crate::Result {
     let mut summary = String::new();
-    let mut list = crate::List::from(dest)?;
+    let mut list = crate::List::from(config, dest)?;
 
     let tasks = if task.is_empty() {
         ask(config, "Add:")?
@@ -49,8 +49,8 @@ fn add_tasks(
         task.join(" ")
     };
 
-    for task in tasks.split('\n') {
-        let mut todo: crate::Task = task.parse()?;
+    for task in tasks.split('\n').filter(|x| !x.trim().is_empty()) {
+        let mut todo: crate::Task = crate::date::resolve(task, config.week_start).parse()?;
 
         if config.date_on_add && todo.create_date.is_none() {
             let today = todo_txt::date::today();
@@ -63,11 +63,16 @@ fn add_tasks(
             todo.priority = pri.try_into().unwrap_or_default();
         }
 
+        if config.id_on_add && !todo.tags.contains_key(crate::ID_TAG) {
+            todo.tags.insert(crate::ID_TAG.to_string(), list.new_id());
+        }
+
         list.push(todo);
 
-        writeln!(summary, "{} {task}", list.len())?;
+        writeln!(summary, "{} {task}", list.len()).ok();
     }
 
+    auto_escalate(config, &mut list);
     list.save()?;
 
     print!("{summary}");
@@ -75,50 +80,183 @@ fn add_tasks(
     Ok(())
 }
 
+pub(crate) fn agenda(
+    config: &crate::Config,
+    crate::opts::Agenda {
+        horizon,
+        starts,
+        filter,
+    }: &crate::opts::Agenda,
+) -> crate::Result {
+    const BUCKETS: [&str; 6] = [
+        "Overdue",
+        "Today",
+        "Tomorrow",
+        "This week",
+        "Later",
+        "No date",
+    ];
+
+    let list = crate::List::from(config, &config.todo_file)?;
+    let width = list.len().max(1).ilog10() as usize + 1;
+
+    let today = todo_txt::date::today();
+    let tomorrow = today + chrono::Days::new(1);
+    let end_of_week = crate::date::start_of_week(today, config.week_start) + chrono::Days::new(6);
+    let horizon = today + chrono::Days::new(horizon.unwrap_or(config.agenda_horizon));
+
+    let bucket = |date: Option<todo_txt::Date>| match date {
+        None => Some(5),
+        Some(date) if date < today => Some(0),
+        Some(date) if date == today => Some(1),
+        Some(date) if date == tomorrow => Some(2),
+        Some(date) if date <= end_of_week => Some(3),
+        Some(date) if date <= horizon => Some(4),
+        Some(_) => None,
+    };
+
+    let mut buckets: [Vec<_>; 6] = Default::default();
+
+    for (item, task) in list.iter().enumerate().map(|(i, x)| (i + 1, x)) {
+        if crate::is_blank(task)
+            || task.finished
+            || !filter_hidden(task)
+            || !filter_term(&task.subject, filter)
+        {
+            continue;
+        }
+
+        if task.threshold_date.is_none_or(|x| x <= today) {
+            if let Some(bucket) = bucket(task.due_date) {
+                let line = print(config, width, (item, task));
+                buckets[bucket].push((task.due_date, item, line));
+            }
+        } else if *starts && let Some(bucket) = bucket(task.threshold_date) {
+            let line = format!("{} (starts)", print(config, width, (item, task)));
+            buckets[bucket].push((task.threshold_date, item, line));
+        }
+    }
+
+    let mut show = 0;
+
+    for (name, mut entries) in BUCKETS.iter().zip(buckets) {
+        if entries.is_empty() {
+            continue;
+        }
+
+        entries.sort();
+        show += entries.len();
+
+        println!("{name}:");
+
+        for (_, _, line) in entries {
+            println!("{line}");
+        }
+    }
+
+    print_summary(&[Summary {
+        file: config.todo_file.clone(),
+        total: list.count(),
+        show,
+    }]);
+
+    Ok(())
+}
+
 pub(crate) fn append(
     config: &crate::Config,
     crate::opts::Append { item, add }: &crate::opts::Append,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
     let text = if add.task.is_empty() {
         ask(config, "Append:")?
     } else {
         add.task.join(" ")
     };
+    let text = crate::date::resolve(&text, config.week_start);
 
-    write!(list.get_mut(item).subject, " {text}")?;
+    write!(list.get_mut(item)?.subject, " {text}").ok();
 
     list.save()?;
 
     if config.verbose > 0 {
-        println!("{item} {}", list.get(item));
+        println!("{item} {}", list.get(item)?);
     }
 
     Ok(())
 }
 
-pub(crate) fn archive(config: &crate::Config) -> crate::Result {
-    let mut todo = crate::List::from(&config.todo_file)?;
-    let mut done = crate::List::from(&config.done_file)?;
-
-    let mut i = 0;
-
-    // @FIXME feature(drain_filter)
-    while i < todo.len() {
-        let index = i + 1;
+pub(crate) fn archive(
+    config: &crate::Config,
+    crate::opts::Archive {
+        older_than,
+        project,
+    }: &crate::opts::Archive,
+) -> crate::Result {
+    use std::collections::btree_map::Entry;
+
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+    let mut archives = std::collections::BTreeMap::<String, crate::List>::new();
+    let today = todo_txt::date::today();
+
+    todo.retain(|x| !crate::is_blank(x));
+
+    let project = project
+        .as_ref()
+        .map(|x| x.trim_start_matches('+').to_string());
+
+    // A family, a task and its subtasks, is archived at once when all its tasks are done.
+    let families = (1..=todo.len())
+        .filter(|x| todo.root(*x) == *x)
+        .map(|x| todo.family(x))
+        .filter(|family| {
+            let task = todo.get(&family[0]);
+
+            family.iter().all(|x| todo.get(x).is_ok_and(|x| x.finished))
+                && task.is_ok_and(|task| {
+                    older_than.is_none_or(|date| task.finish_date.is_some_and(|x| x < date))
+                        && project.as_ref().is_none_or(|x| task.projects.contains(x))
+                })
+        })
+        .collect::<Vec<_>>();
+
+    for family in &families {
+        let finish_date = todo.get(&family[0])?.finish_date.unwrap_or(today);
+
+        let done = match archives.entry(config.archive_file(finish_date)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let list = crate::List::from(config, entry.key())?;
+                entry.insert(list)
+            }
+        };
 
-        if todo.get(&index).finished {
-            let mut task = todo.remove(index);
+        for item in family {
+            let mut task = todo.get(item)?.clone();
             archive_note(config, &mut task)?;
+
             done.push(task);
-        } else {
-            i += 1;
         }
     }
 
+    let archived = families
+        .into_iter()
+        .flatten()
+        .collect::<std::collections::HashSet<_>>();
+    let mut item = 0;
+
+    todo.retain(|_| {
+        item += 1;
+        !archived.contains(&item)
+    });
+
     todo.save()?;
-    done.save()?;
+
+    for done in archives.values() {
+        done.save()?;
+    }
 
     if config.verbose > 0 {
         println!("TODO: {} archived", config.todo_file);
@@ -135,29 +273,194 @@ fn archive_note(_: &crate::Config, _: &mut crate::Task) -> crate::Result {
 #[cfg(feature = "extended")]
 fn archive_note(config: &crate::Config, task: &mut crate::Task) -> crate::Result {
     if let Some(note) = task.note.content() {
-        use std::io::Write;
+        crate::append_file(config, &config.note_archive, &format!("{note}\n"))?;
+    }
+
+    if !config.dry_run {
+        task.note.delete()?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Mark {
+    Due,
+    Start,
+    Done,
+}
+
+impl Mark {
+    fn symbol(self) -> char {
+        match self {
+            Self::Due => '!',
+            Self::Start => '>',
+            Self::Done => 'x',
+        }
+    }
+}
+
+pub(crate) fn cal(
+    config: &crate::Config,
+    crate::opts::Cal { date, week }: &crate::opts::Cal,
+) -> crate::Result {
+    use chrono::Datelike as _;
+
+    let today = todo_txt::date::today();
+
+    let date = match date {
+        Some(date) => todo_txt::Date::parse_from_str(&format!("{date}-01"), "%Y-%m-%d")
+            .or_else(|_| todo_txt::Date::parse_from_str(date, "%Y-%m-%d"))
+            .ok()
+            .or_else(|| crate::date::parse(date, today, config.week_start))
+            .ok_or_else(|| crate::Error::Parse(format!("Invalid date: {date}")))?,
+        None => today,
+    };
+
+    let todo = crate::List::from(config, &config.todo_file)?;
+    let width = todo.len().max(1).ilog10() as usize + 1;
+
+    let mut days = std::collections::BTreeMap::<todo_txt::Date, Vec<_>>::new();
+
+    for (item, task) in todo.iter().enumerate().map(|(i, x)| (i + 1, x)) {
+        if crate::is_blank(task) || !filter_hidden(task) {
+            continue;
+        }
+
+        if task.finished {
+            if let Some(finish_date) = task.finish_date {
+                days.entry(finish_date)
+                    .or_default()
+                    .push((Mark::Done, item, task.clone()));
+            }
+            continue;
+        }
+
+        if let Some(due_date) = task.due_date {
+            days.entry(due_date)
+                .or_default()
+                .push((Mark::Due, item, task.clone()));
+        }
+
+        if let Some(threshold_date) = task.threshold_date {
+            days.entry(threshold_date)
+                .or_default()
+                .push((Mark::Start, item, task.clone()));
+        }
+    }
+
+    for done_file in config.done_files() {
+        for task in crate::List::from(config, &done_file)?.iter() {
+            if let Some(finish_date) = task.finish_date {
+                days.entry(finish_date)
+                    .or_default()
+                    .push((Mark::Done, 0, task.clone()));
+            }
+        }
+    }
+
+    if *week {
+        let start = crate::date::start_of_week(date, config.week_start);
 
-        let mut archive = std::fs::File::options()
-            .append(true)
-            .open(&config.note_archive)?;
+        for day in start.iter_days().take(7) {
+            println!("{}", day.format("%a %Y-%m-%d"));
 
-        archive.write_all(format!("{note}\n").as_bytes())?;
+            let mut entries = days.get(&day).cloned().unwrap_or_default();
+            entries.sort_by_key(|(mark, item, _)| (*mark, *item));
+
+            for (mark, item, task) in entries {
+                println!(
+                    "  {} {}",
+                    mark.symbol(),
+                    print(config, width, (item, &task))
+                );
+            }
+        }
+
+        return Ok(());
     }
 
-    task.note.delete()?;
+    let first = crate::date::start_of_month(date);
+    let last = crate::date::end_of_month(date);
+
+    let title = format!("{:^27}", first.format("%B %Y").to_string());
+    println!("{}", title.trim_end());
+
+    let mut weekday = config.week_start;
+    let mut header = Vec::new();
+
+    for _ in 0..7 {
+        header.push(format!("{:<3}", &weekday.to_string()[..2]));
+        weekday = weekday.succ();
+    }
+
+    println!("{}", header.join(" ").trim_end());
+
+    let mut row = vec!["   ".to_string(); first.weekday().days_since(config.week_start) as usize];
+
+    for day in first.iter_days().take_while(|x| *x <= last) {
+        let entries = days.get(&day).map(Vec::as_slice).unwrap_or_default();
+
+        let mark = entries
+            .iter()
+            .map(|(mark, _, _)| *mark)
+            .min()
+            .map_or(' ', Mark::symbol);
+        let cell = format!("{:>2}{mark}", day.day());
+
+        let priority = entries
+            .iter()
+            .map(|(_, _, task)| task.priority.clone())
+            .max()
+            .filter(|x| !x.is_lowest());
+
+        row.push(match priority {
+            Some(priority) => config.colors.for_pri(&priority).colorize(&cell),
+            None => cell,
+        });
+
+        if row.len() == 7 || day == last {
+            println!("{}", row.join(" ").trim_end());
+            row.clear();
+        }
+    }
+
+    println!("! due  > starts  x done");
 
     Ok(())
 }
 
-pub(crate) fn deduplicate(config: &crate::Config) -> crate::Result {
-    let mut todo = crate::List::from(&config.todo_file)?;
-    let original_task_num = todo.len();
+pub(crate) fn deduplicate(
+    config: &crate::Config,
+    crate::opts::Deduplicate { normalize, similar }: &crate::opts::Deduplicate,
+) -> crate::Result {
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+    let original_task_num = todo.count();
 
-    todo.sort();
-    todo.dedup();
-    todo.save()?;
+    let mut seen = std::collections::HashSet::new();
+
+    let duplicates = todo
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !crate::is_blank(x))
+        .filter(|(_, x)| {
+            let key = if *normalize {
+                normalized(x)
+            } else {
+                x.to_string()
+            };
+
+            !seen.insert(key)
+        })
+        .map(|(i, _)| i + 1)
+        .collect::<Vec<_>>();
+
+    // From the end, so that removing a line does not shift the next ones.
+    for item in duplicates.into_iter().rev() {
+        todo.delete(item, config.preserve_line_numbers)?;
+    }
 
-    let deduplicate_num = original_task_num - todo.len();
+    let deduplicate_num = original_task_num - todo.count();
 
     if deduplicate_num == 0 {
         println!("TODO: No duplicate tasks found");
@@ -165,21 +468,228 @@ pub(crate) fn deduplicate(config: &crate::Config) -> crate::Result {
         println!("TODO: {deduplicate_num} duplicate task(s) removed");
     }
 
+    if let Some(threshold) = similar {
+        let merged = merge_similar(config, &mut todo, *threshold)?;
+
+        if merged > 0 {
+            println!("TODO: {merged} similar task(s) merged");
+        }
+    }
+
+    todo.save()?;
+
     Ok(())
 }
 
+/// Task text without creation date and priority, with collapsed whitespace and lowercased.
+fn normalized(task: &crate::Task) -> String {
+    let mut task = task.clone();
+    task.priority = todo_txt::Priority::lowest();
+    task.create_date = None;
+
+    task.to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn merge_similar(
+    config: &crate::Config,
+    todo: &mut crate::List,
+    threshold: f32,
+) -> crate::Result<usize> {
+    let mut merged = 0;
+    let mut i = 1;
+
+    while i <= todo.len() {
+        let mut j = i + 1;
+
+        while j <= todo.len() {
+            // Blank lines are skipped.
+            let (Ok(a), Ok(b)) = (todo.get(&i), todo.get(&j)) else {
+                j += 1;
+                continue;
+            };
+
+            let a = normalized(a).chars().collect::<Vec<_>>();
+            let b = normalized(b).chars().collect::<Vec<_>>();
+
+            if difflib::sequencematcher::SequenceMatcher::new(&a, &b).ratio() >= threshold {
+                println!("{i} {}", todo.get(&i)?);
+                println!("{j} {}", todo.get(&j)?);
+
+                if confirm(config, &format!("Merge {j} into {i}?"))? {
+                    let other = todo.delete(j, config.preserve_line_numbers)?;
+                    merge(todo.get_mut(&i)?, other);
+                    merged += 1;
+                    continue;
+                }
+            }
+
+            j += 1;
+        }
+
+        i += 1;
+    }
+
+    Ok(merged)
+}
+
+/// Keeps `task` and adds what only `other` has: contexts, projects, hashtags, tags, dates and a
+/// higher priority.
+fn merge(task: &mut crate::Task, other: crate::Task) {
+    for word in other.subject.split_whitespace() {
+        if word.starts_with(['+', '@', '#']) && !task.subject.split_whitespace().any(|x| x == word)
+        {
+            write!(task.subject, " {word}").ok();
+        }
+    }
+
+    for (key, value) in &other.tags {
+        task.tags
+            .entry(key.clone())
+            .or_insert_with(|| value.clone());
+    }
+
+    task.due_date = task.due_date.or(other.due_date);
+    task.threshold_date = task.threshold_date.or(other.threshold_date);
+
+    if other.priority > task.priority {
+        task.priority = other.priority.clone();
+    }
+}
+
+pub(crate) fn deps(
+    config: &crate::Config,
+    crate::opts::Item { item }: &crate::opts::Item,
+) -> crate::Result {
+    let list = crate::List::from(config, &config.todo_file)?;
+    let item = list.resolve(item)?;
+    let width = list.len().max(1).ilog10() as usize + 1;
+
+    println!("{}", print(config, width, (item, list.get(&item)?)));
+
+    let graph = list.dependencies();
+    let mut cycles = Vec::new();
+    print_deps(
+        config,
+        &list,
+        &graph,
+        width,
+        &mut vec![item],
+        "",
+        &mut cycles,
+    );
+
+    for cycle in cycles {
+        let cycle = cycle
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" -> ");
+
+        println!("TODO: Dependency cycle {cycle}.");
+    }
+
+    Ok(())
+}
+
+/// Prints the dependencies of the last task of `path` as a tree, collecting the cycles instead of
+/// following them.
+fn print_deps(
+    config: &crate::Config,
+    list: &crate::List,
+    graph: &crate::Dependencies,
+    width: usize,
+    path: &mut Vec<usize>,
+    indent: &str,
+    cycles: &mut Vec<Vec<usize>>,
+) {
+    let Some(item) = path.last() else {
+        return;
+    };
+
+    let deps = graph.of(*item);
+
+    for (i, dep) in deps.iter().enumerate() {
+        let Ok(task) = list.get(dep) else {
+            continue;
+        };
+
+        let (branch, next) = if i + 1 == deps.len() {
+            ("└─ ", "   ")
+        } else {
+            ("├─ ", "│  ")
+        };
+        let line = print(config, width, (*dep, task));
+
+        if let Some(start) = path.iter().position(|x| x == dep) {
+            println!("{indent}{branch}{line} (cycle)");
+
+            let mut cycle = path[start..].to_vec();
+            cycle.push(*dep);
+            cycles.push(cycle);
+
+            continue;
+        }
+
+        println!("{indent}{branch}{line}");
+
+        path.push(*dep);
+        print_deps(
+            config,
+            list,
+            graph,
+            width,
+            path,
+            &format!("{indent}{next}"),
+            cycles,
+        );
+        path.pop();
+    }
+}
+
 pub(crate) fn del(
     config: &crate::Config,
     crate::opts::Del { item, filter }: &crate::opts::Del,
 ) -> crate::Result {
-    if filter.term.is_none() {
-        if !confirm(config, &format!("Delete {item}"))? {
-            return Ok(());
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+    let item = &todo.resolve(item)?;
+
+    if let Some(term) = &filter.term {
+        let task = todo.get_mut(item)?;
+        let old_task = task.to_string();
+
+        let mut new_task = format!(" {old_task} ")
+            .replace(&format!(" {term} "), " ")
+            .trim()
+            .to_string();
+
+        if new_task == old_task {
+            new_task = old_task.replace(term.as_str(), "");
         }
 
-        let mut todo = crate::List::from(&config.todo_file)?;
+        if new_task == old_task {
+            return Err(crate::Error::TermNotFound(term.clone()));
+        }
 
-        let task = todo.remove(*item);
+        *task = new_task.parse()?;
+        todo.save()?;
+
+        if config.verbose > 0 {
+            println!("{item} {old_task}");
+            println!("TODO: Removed '{term}' from task.");
+            println!("{item} {new_task}");
+        }
+    } else {
+        todo.get(item)?;
+
+        if !confirm(config, &format!("Delete {item}"))? {
+            return Err(crate::Error::Cancelled);
+        }
+
+        let task = todo.delete(*item, config.preserve_line_numbers)?;
 
         todo.save()?;
 
@@ -187,8 +697,6 @@ pub(crate) fn del(
             println!("{item} {task}");
             println!("TODO: {item} deleted.");
         }
-    } else {
-        todo!()
     }
 
     Ok(())
@@ -198,13 +706,14 @@ pub(crate) fn delpri(
     config: &crate::Config,
     crate::opts::Item { item }: &crate::opts::Item,
 ) -> crate::Result {
-    let mut todo = crate::List::from(&config.todo_file)?;
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+    let item = &todo.resolve(item)?;
 
-    todo.get_mut(item).priority = todo_txt::Priority::lowest();
+    todo.get_mut(item)?.priority = todo_txt::Priority::lowest();
     todo.save()?;
 
     if config.verbose > 0 {
-        println!("{item} {}", todo.get(item));
+        println!("{item} {}", todo.get(item)?);
         println!("TODO: {item} deprioritized.");
     }
 
@@ -215,29 +724,118 @@ pub(crate) fn done(
     config: &crate::Config,
     crate::opts::Item { item }: &crate::opts::Item,
 ) -> crate::Result {
-    let mut todo = crate::List::from(&config.todo_file)?;
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+    let item = &todo.resolve(item)?;
 
-    let task = if config.auto_archive {
-        let mut task = todo.remove(*item);
-        task.finished = true;
+    let open_children = todo
+        .children(*item)
+        .iter()
+        .filter(|x| todo.get(x).is_ok_and(|x| !x.finished))
+        .count();
+
+    if open_children > 0
+        && !confirm(
+            config,
+            &format!("Mark {item} with {open_children} open subtask(s) as done"),
+        )?
+    {
+        return Err(crate::Error::Cancelled);
+    }
 
-        let mut done = crate::List::from(&config.done_file)?;
+    let graph = todo.dependencies();
+    let unblocked = graph
+        .dependents(*item)
+        .iter()
+        .copied()
+        .filter(|x| todo.get(x).is_ok_and(|x| !x.finished))
+        .filter(|x| {
+            graph
+                .of(*x)
+                .iter()
+                .all(|dep| dep == item || todo.get(dep).is_ok_and(|x| x.finished))
+        })
+        .collect::<Vec<_>>();
+
+    // Tasks with a parent or subtasks stay in todo.txt until their whole family is done.
+    let family = todo.family(todo.root(*item));
+    let alone = family.len() == 1;
+
+    let task = if config.auto_archive && alone {
+        let mut task = todo.delete(*item, config.preserve_line_numbers)?;
+        task.complete();
+
+        let finish_date = task.finish_date.unwrap_or_else(todo_txt::date::today);
+        let mut done = crate::List::from(config, &config.archive_file(finish_date))?;
         done.push(task.clone());
         done.save()?;
 
         task
     } else {
-        todo.get_mut(item).finished = true;
+        todo.get_mut(item)?.complete();
 
-        todo.get(item).clone()
+        todo.get(item)?.clone()
+    };
+
+    let archived = if !config.auto_archive {
+        Vec::new()
+    } else if alone {
+        family
+    } else if family.iter().all(|x| todo.get(x).is_ok_and(|x| x.finished)) {
+        archive_family(config, &mut todo, &family)?;
+        family
+    } else {
+        Vec::new()
     };
 
     recurrence(config, &mut todo, &task);
+    auto_escalate(config, &mut todo);
     todo.save()?;
 
     if config.verbose > 0 {
         println!("{item} {task}");
         println!("TODO: {item} marked as done.");
+
+        for dependent in unblocked {
+            let dependent = if config.preserve_line_numbers {
+                dependent
+            } else {
+                dependent - archived.iter().filter(|x| **x < dependent).count()
+            };
+
+            println!("TODO: {dependent} unblocked.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves a task and its subtasks to the archive of the task, like `archive` does.
+fn archive_family(
+    config: &crate::Config,
+    todo: &mut crate::List,
+    family: &[usize],
+) -> crate::Result {
+    let finish_date = todo
+        .get(&family[0])?
+        .finish_date
+        .unwrap_or_else(todo_txt::date::today);
+    let mut done = crate::List::from(config, &config.archive_file(finish_date))?;
+
+    for item in family {
+        let mut task = todo.get(item)?.clone();
+        archive_note(config, &mut task)?;
+
+        done.push(task);
+    }
+
+    done.save()?;
+
+    let mut lines = family.to_vec();
+    lines.sort_unstable();
+
+    // From the end, so that removing a line does not shift the next ones.
+    for item in lines.into_iter().rev() {
+        todo.delete(item, config.preserve_line_numbers)?;
     }
 
     Ok(())
@@ -248,83 +846,453 @@ fn recurrence(_: &crate::Config, _: &mut crate::List, _: &crate::Task) {}
 
 #[cfg(feature = "extended")]
 fn recurrence(config: &crate::Config, todo: &mut crate::List, task: &crate::Task) {
-    if let Some(ref recurrence) = task.recurrence {
-        let due = if recurrence.strict
+    use crate::recurrence::{COUNT_TAG, EVERY_TAG, UNTIL_TAG};
+
+    let today = todo_txt::date::today();
+    let count = task.tags.get(COUNT_TAG).and_then(|x| x.parse::<u32>().ok());
+
+    if count.is_some_and(|x| x <= 1) {
+        return;
+    }
+
+    let every = task
+        .tags
+        .get(EVERY_TAG)
+        .and_then(|x| x.parse::<crate::recurrence::Every>().ok());
+
+    // Occurrences already past due when the task is completed late are skipped.
+    let (due, threshold_date) = if let Some(every) = every {
+        let base = task.due_date.unwrap_or(today);
+        let Some(mut due) = every.next(base) else {
+            return;
+        };
+
+        while due < today {
+            let Some(next) = every.next(due) else {
+                return;
+            };
+
+            due = next;
+        }
+
+        (due, task.threshold_date.map(|x| x + (due - base)))
+    } else if let Some(ref recurrence) = task.recurrence {
+        let mut due = if recurrence.strict
             && let Some(due) = task.due_date
         {
             due
         } else {
-            todo_txt::date::today()
+            today
+        };
+        let mut threshold_date = task.threshold_date;
+
+        loop {
+            let next = recurrence.clone() + due;
+
+            // A zero interval (rec:0d) never reaches today.
+            if next <= due {
+                return;
+            }
+
+            due = next;
+            threshold_date = threshold_date.map(|x| recurrence.clone() + x);
+
+            if due >= today {
+                break;
+            }
+        }
+
+        (due, threshold_date)
+    } else {
+        return;
+    };
+
+    let until = task
+        .tags
+        .get(UNTIL_TAG)
+        .and_then(|x| todo_txt::Date::parse_from_str(x, "%Y-%m-%d").ok());
+
+    if until.is_some_and(|x| due > x) {
+        return;
+    }
+
+    let mut new = task.clone();
+    new.uncomplete();
+    if config.date_on_add {
+        new.create_date = Some(today);
+    }
+    new.due_date = Some(due);
+    new.threshold_date = threshold_date;
+
+    if let Some(count) = count {
+        new.tags
+            .insert(COUNT_TAG.to_string(), (count - 1).to_string());
+    }
+
+    if new.tags.contains_key(crate::ID_TAG) {
+        let id = todo.new_id();
+        new.tags.insert(crate::ID_TAG.to_string(), id);
+    }
+
+    todo.push(new);
+}
+
+#[cfg(not(feature = "extended"))]
+fn remove_successor(
+    _: &crate::Config,
+    _: &mut crate::List,
+    _: &crate::Task,
+) -> crate::Result<Option<crate::Task>> {
+    Ok(None)
+}
+
+/// Removes the open task spawned by `recurrence` when `task` was marked as done.
+#[cfg(feature = "extended")]
+fn remove_successor(
+    config: &crate::Config,
+    todo: &mut crate::List,
+    task: &crate::Task,
+) -> crate::Result<Option<crate::Task>> {
+    if task.recurrence.is_none() && !task.tags.contains_key(crate::recurrence::EVERY_TAG) {
+        return Ok(None);
+    }
+
+    let index = todo.iter().position(|x| {
+        !x.finished
+            && x.subject == task.subject
+            && x.recurrence == task.recurrence
+            && x.due_date > task.due_date
+    });
+
+    index
+        .map(|x| todo.delete(x + 1, config.preserve_line_numbers))
+        .transpose()
+}
+
+#[cfg(feature = "extended")]
+pub(crate) fn env(config: &crate::Config) -> crate::Result {
+    use envir::Serialize as _;
+
+    let env = config.collect();
+
+    for (key, value) in env {
+        println!("export {key}='{value}'");
+    }
+
+    Ok(())
+}
+
+pub(crate) fn escalate(config: &crate::Config) -> crate::Result {
+    let mut list = crate::List::from(config, &config.todo_file)?;
+
+    escalate_tasks(config, &mut list);
+
+    list.save()
+}
+
+fn auto_escalate(config: &crate::Config, list: &mut crate::List) -> bool {
+    config.auto_escalate && escalate_tasks(config, list)
+}
+
+/// Applies `TODOTXT_ESCALATION` to the open tasks of `list`, returning whether a priority was
+/// raised.
+fn escalate_tasks(config: &crate::Config, list: &mut crate::List) -> bool {
+    let today = todo_txt::date::today();
+    let mut escalated = false;
+
+    for (item, task) in list.iter_mut().enumerate().map(|(i, x)| (i + 1, x)) {
+        let Some(due_date) = task.due_date.filter(|_| !task.finished) else {
+            continue;
+        };
+
+        let Some(priority) = config.escalation.priority((due_date - today).num_days()) else {
+            continue;
+        };
+
+        if priority <= task.priority {
+            continue;
+        }
+
+        let oldpri = std::mem::replace(&mut task.priority, priority);
+        escalated = true;
+
+        if config.verbose > 0 {
+            println!("{item} {task}");
+
+            if oldpri.is_lowest() {
+                println!("TODO: {item} escalated to ({}).", task.priority);
+            } else {
+                println!(
+                    "TODO: {item} escalated from ({oldpri}) to ({}).",
+                    task.priority
+                );
+            }
+        }
+    }
+
+    escalated
+}
+
+/// Sets the flag of the tasks to `flagged`, or toggles it when `None`.
+#[cfg(feature = "extended")]
+pub(crate) fn flag(
+    config: &crate::Config,
+    items: &[crate::opts::TaskRef],
+    flagged: Option<bool>,
+) -> crate::Result {
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let items = items
+        .iter()
+        .map(|x| list.resolve(x))
+        .collect::<crate::Result<Vec<_>>>()?;
+
+    for item in &items {
+        let task = list.get_mut(item)?;
+        task.flagged = flagged.unwrap_or(!task.flagged);
+    }
+
+    list.save()?;
+
+    if config.verbose > 0 {
+        for item in &items {
+            let task = list.get(item)?;
+
+            println!("{item} {task}");
+
+            if task.flagged {
+                println!("TODO: {item} flagged.");
+            } else {
+                println!("TODO: {item} unflagged.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Streaks and completion rates of the open recurring tasks.
+#[cfg(feature = "extended")]
+pub(crate) fn habits(config: &crate::Config, filter: &crate::opts::Filter) -> crate::Result {
+    const HABIT_TAG: &str = "habit";
+    const SPARKLINE_DAYS: u64 = 30;
+
+    let name = |task: &crate::Task| {
+        task.tags
+            .get(HABIT_TAG)
+            .cloned()
+            .unwrap_or_else(|| task.subject.clone())
+    };
+
+    let todo = crate::List::from(config, &config.todo_file)?;
+    let mut habits = Vec::new();
+
+    for task in todo.iter() {
+        let recurring =
+            task.recurrence.is_some() || task.tags.contains_key(crate::recurrence::EVERY_TAG);
+
+        if recurring && !task.finished && filter_term(&task.subject, filter) {
+            let name = name(task);
+
+            if !habits.contains(&name) {
+                habits.push(name);
+            }
+        }
+    }
+
+    let mut completions =
+        std::collections::HashMap::<String, std::collections::BTreeSet<todo_txt::Date>>::new();
+
+    let mut done = todo.iter().cloned().collect::<Vec<_>>();
+
+    for done_file in config.done_files() {
+        done.extend(crate::List::from(config, &done_file)?.iter().cloned());
+    }
+
+    for task in done.iter().filter(|x| x.finished) {
+        if let Some(finish_date) = task.finish_date {
+            completions
+                .entry(name(task))
+                .or_default()
+                .insert(finish_date);
+        }
+    }
+
+    let today = todo_txt::date::today();
+
+    for habit in habits {
+        let days = completions.remove(&habit).unwrap_or_default();
+
+        let mut best = 0;
+        let mut run = 0;
+        let mut previous = None;
+
+        for day in &days {
+            run = if previous.and_then(|x: todo_txt::Date| x.succ_opt()) == Some(*day) {
+                run + 1
+            } else {
+                1
+            };
+            best = best.max(run);
+            previous = Some(*day);
+        }
+
+        // Today still counts as kept until it is over.
+        let mut day = if days.contains(&today) {
+            today
+        } else {
+            today - chrono::Days::new(1)
         };
+        let mut current = 0;
 
-        let mut new = task.clone();
-        new.uncomplete();
-        if config.date_on_add {
-            new.create_date = Some(todo_txt::date::today());
+        while days.contains(&day) {
+            current += 1;
+            day = day - chrono::Days::new(1);
         }
-        new.due_date = Some(recurrence.clone() + due);
 
-        if let Some(threshold_date) = task.threshold_date {
-            new.threshold_date = Some(recurrence.clone() + threshold_date);
-        }
+        let rate = |n: u64| {
+            let since = today - chrono::Days::new(n);
+
+            days.range(since.succ_opt().unwrap_or(since)..=today)
+                .count()
+                * 100
+                / n as usize
+        };
 
-        todo.push(new);
+        let sparkline = (0..SPARKLINE_DAYS)
+            .rev()
+            .map(|x| {
+                if days.contains(&(today - chrono::Days::new(x))) {
+                    '█'
+                } else {
+                    '▁'
+                }
+            })
+            .collect::<String>();
+
+        println!("{habit}");
+        println!(
+            "  {sparkline}  streak {current} (best {best})  30d {}%  90d {}%",
+            rate(30),
+            rate(90)
+        );
     }
+
+    Ok(())
 }
 
+/// Sets the hidden attribute of the tasks.
 #[cfg(feature = "extended")]
-pub(crate) fn env(config: &crate::Config) -> crate::Result {
-    use envir::Serialize as _;
-
-    let env = config.collect();
+pub(crate) fn hide(
+    config: &crate::Config,
+    items: &[crate::opts::TaskRef],
+    hidden: bool,
+) -> crate::Result {
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let items = items
+        .iter()
+        .map(|x| list.resolve(x))
+        .collect::<crate::Result<Vec<_>>>()?;
 
-    for (key, value) in env {
-        println!("export {key}='{value}'");
+    for item in &items {
+        list.get_mut(item)?.hidden = hidden;
     }
 
-    Ok(())
-}
+    list.save()?;
 
-pub(crate) fn flag(config: &crate::Config, item: usize) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
-    let task = list.get_mut(&item);
-    task.flagged = true;
+    if config.verbose > 0 {
+        for item in &items {
+            println!("{item} {}", list.get(item)?);
 
-    list.save()
+            if hidden {
+                println!("TODO: {item} hidden.");
+            } else {
+                println!("TODO: {item} unhidden.");
+            }
+        }
+    }
+
+    Ok(())
 }
 
-pub(crate) fn listflag(config: &crate::Config) -> crate::Result {
-    let summary = print_list(config, true, &config.todo_file, |(_, x)| x.flagged)?;
+#[cfg(feature = "extended")]
+pub(crate) fn listflag(config: &crate::Config, filter: &crate::opts::Filter) -> crate::Result {
+    let summary = print_list(config, true, &config.todo_file, |(_, x)| {
+        x.flagged && filter_list(x, filter)
+    })?;
 
     print_summary(&[summary]);
 
     Ok(())
 }
 
-pub(crate) fn list(config: &crate::Config, filter: &crate::opts::Filter) -> crate::Result {
-    let now = todo_txt::date::today();
+pub(crate) fn list(
+    config: &crate::Config,
+    crate::opts::List {
+        blocked,
+        filter,
+        show_hidden,
+        snoozed,
+        tree,
+        ..
+    }: &crate::opts::List,
+) -> crate::Result {
+    let today = todo_txt::date::today();
 
-    let summary = print_list(config, true, &config.todo_file, |(_, x)| {
-        !x.finished
-            && filter_hidden(x)
-            && filter_term(&x.subject, filter)
-            && now >= x.threshold_date.unwrap_or(now)
-    })?;
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+
+    if auto_escalate(config, &mut todo) {
+        todo.save()?;
+    }
+
+    let graph = todo.dependencies();
+
+    let predicate = |(item, x): &(usize, &crate::Task)| {
+        if !*blocked && graph.is_blocked(*item) {
+            false
+        } else if *snoozed {
+            !x.finished
+                && (*show_hidden || filter_hidden(x))
+                && filter_term(&x.subject, filter)
+                && x.threshold_date.is_some_and(|x| x > today)
+        } else {
+            (*show_hidden || filter_hidden(x)) && filter_open(x, filter)
+        }
+    };
+
+    let summary = if *tree {
+        print_tree(config, &todo, predicate)?
+    } else {
+        print_list(config, true, &config.todo_file, predicate)?
+    };
 
     print_summary(&[summary]);
 
     Ok(())
 }
 
+fn filter_list(task: &crate::Task, filter: &crate::opts::Filter) -> bool {
+    filter_hidden(task) && filter_open(task, filter)
+}
+
+/// Open tasks matching TERM(s) whose threshold date is reached.
+fn filter_open(task: &crate::Task, filter: &crate::opts::Filter) -> bool {
+    let now = todo_txt::date::today();
+
+    !task.finished
+        && filter_term(&task.subject, filter)
+        && now >= task.threshold_date.unwrap_or(now)
+}
+
 pub(crate) fn listall(config: &crate::Config, filter: &crate::opts::Filter) -> crate::Result {
-    let summary = vec![
-        print_list(config, true, &config.todo_file, |(_, x)| {
-            filter_term(&x.subject, filter)
-        })?,
-        print_list(config, false, &config.done_file, |(_, x)| {
+    let mut summary = vec![print_list(config, true, &config.todo_file, |(_, x)| {
+        filter_term(&x.subject, filter)
+    })?];
+
+    for done_file in config.done_files() {
+        summary.push(print_list(config, false, &done_file, |(_, x)| {
             filter_term(&x.subject, filter)
-        })?,
-    ];
+        })?);
+    }
 
     print_summary(&summary);
 
@@ -343,10 +1311,40 @@ pub(crate) fn listaddons(config: &crate::Config) -> crate::Result {
     Ok(())
 }
 
+pub(crate) fn listtags(
+    config: &crate::Config,
+    crate::opts::ListTags { key, filter }: &crate::opts::ListTags,
+) -> crate::Result {
+    let list = crate::List::from(config, &config.todo_file)?;
+    let mut counts = std::collections::BTreeMap::<&str, usize>::new();
+
+    for task in list.iter().filter(|x| filter_list(x, filter)) {
+        for (k, v) in &task.tags {
+            match key {
+                Some(key) if key == k => *counts.entry(v).or_default() += 1,
+                Some(_) => (),
+                None => *counts.entry(k).or_default() += 1,
+            }
+        }
+    }
+
+    let color = &config.colors.meta;
+
+    for (name, count) in counts {
+        println!("{} ({count} tasks)", color.colorize(name));
+    }
+
+    Ok(())
+}
+
 pub(crate) fn listfile(
     config: &crate::Config,
     crate::opts::ListFile { src, filter }: &crate::opts::ListFile,
 ) -> crate::Result {
+    let Some(src) = src else {
+        return listfiles(config);
+    };
+
     let file = format!("{}/{}", config.todo_dir, src);
     let summary = print_list(config, true, &file, |(_, x)| {
         filter_term(&x.subject, filter)
@@ -357,6 +1355,35 @@ pub(crate) fn listfile(
     Ok(())
 }
 
+fn listfiles(config: &crate::Config) -> crate::Result {
+    for path in txt_files(config)? {
+        let list = crate::List::from(config, &path.to_string_lossy())?;
+        let done = list.iter().filter(|x| x.finished).count();
+        let modified = chrono::DateTime::<chrono::Local>::from(path.metadata()?.modified()?);
+
+        println!(
+            "{}: {} tasks ({} open, {done} done), modified {}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            list.count(),
+            list.count() - done,
+            modified.format("%Y-%m-%d %H:%M"),
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn txt_files(config: &crate::Config) -> crate::Result<Vec<std::path::PathBuf>> {
+    let mut files = std::fs::read_dir(&config.todo_dir)?
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| x.is_file() && x.extension().is_some_and(|ext| ext == "txt"))
+        .collect::<Vec<_>>();
+
+    files.sort();
+
+    Ok(files)
+}
+
 fn print_list<P>(
     config: &crate::Config,
     with_id: bool,
@@ -366,14 +1393,15 @@ fn print_list<P>(
 where
     P: FnMut(&(usize, &crate::Task)) -> bool,
 {
-    let list = crate::List::from(file)?;
-    let total = list.len();
+    let list = crate::List::from(config, file)?;
+    let total = list.count();
 
-    let width = total.ilog10() as usize + 1;
+    let width = list.len().max(1).ilog10() as usize + 1;
 
     let tasks = list
         .iter()
         .enumerate()
+        .filter(|(_, task)| !crate::is_blank(task))
         .map(|(id, task)| if with_id { (id + 1, task) } else { (0, task) })
         .filter(predicate)
         .map(|task| print(config, width, task))
@@ -397,6 +1425,99 @@ where
     })
 }
 
+/// Like `print_list`, with subtasks indented under their parent followed by the progress of
+/// their children. The sort command is not applied to keep the hierarchy.
+fn print_tree<P>(
+    config: &crate::Config,
+    list: &crate::List,
+    mut predicate: P,
+) -> crate::Result<Summary>
+where
+    P: FnMut(&(usize, &crate::Task)) -> bool,
+{
+    let width = list.len().max(1).ilog10() as usize + 1;
+
+    let visible = (1..=list.len())
+        .filter(|x| list.get(x).is_ok_and(|task| predicate(&(*x, task))))
+        .collect::<std::collections::BTreeSet<_>>();
+
+    let mut seen = std::collections::HashSet::new();
+    let mut lines = Vec::new();
+
+    for item in &visible {
+        if list.parent(*item).is_none_or(|x| !visible.contains(&x)) {
+            tree_lines(
+                config,
+                list,
+                width,
+                &visible,
+                (*item, 0),
+                &mut seen,
+                &mut lines,
+            );
+        }
+    }
+
+    let lines = lines.iter().map(|x| format!("{x}\n")).collect();
+    let tasks = exec(&config.final_filter, lines)?;
+
+    print!("{tasks}");
+
+    Ok(Summary {
+        file: config.todo_file.clone(),
+        total: list.count(),
+        show: tasks.lines().count(),
+    })
+}
+
+fn tree_lines(
+    config: &crate::Config,
+    list: &crate::List,
+    width: usize,
+    visible: &std::collections::BTreeSet<usize>,
+    (item, depth): (usize, usize),
+    seen: &mut std::collections::HashSet<usize>,
+    lines: &mut Vec<String>,
+) {
+    let Ok(task) = list.get(&item) else {
+        return;
+    };
+
+    if !seen.insert(item) {
+        return;
+    }
+
+    let children = list.children(item);
+    let mut line = format!(
+        "{}{}",
+        "  ".repeat(depth),
+        print(config, width, (item, task))
+    );
+
+    if !children.is_empty() {
+        let done = children
+            .iter()
+            .filter(|x| list.get(x).is_ok_and(|x| x.finished))
+            .count();
+
+        write!(line, " ({done}/{} done)", children.len()).ok();
+    }
+
+    lines.push(line);
+
+    for child in children.into_iter().filter(|x| visible.contains(x)) {
+        tree_lines(
+            config,
+            list,
+            width,
+            visible,
+            (child, depth + 1),
+            seen,
+            lines,
+        );
+    }
+}
+
 fn print_summary(summary: &[Summary]) {
     let mut show = 0;
     let mut total = 0;
@@ -550,7 +1671,16 @@ fn print(config: &crate::Config, width: usize, (id, task): (usize, &crate::Task)
         output.push_str(&color.colorize(&format!(" rec:{recurrence}")));
     }
 
+    #[cfg(feature = "extended")]
+    if task.hidden {
+        output.push_str(&color.colorize(" h:1"));
+    }
+
     for (key, value) in &task.tags {
+        if key == crate::ID_TAG && !config.show_ids {
+            continue;
+        }
+
         output.push_str(&color.colorize(&format!(" {key}:{value}")));
     }
 
@@ -586,30 +1716,131 @@ fn print_date(config: &crate::Config, date: &todo_txt::Date) -> String {
     config.colors.date.colorize(&s)
 }
 
-macro_rules! list_tag {
-    ($ty:ident, $config:ident, $filter:ident) => {{
-        let todo = crate::List::from(&$config.todo_file)?;
+#[derive(Default)]
+struct TagCount {
+    open: usize,
+    done: usize,
+    overdue: usize,
+}
 
-        let mut tags = todo
-            .iter()
-            .map(|x| x.$ty.clone())
-            .flatten()
-            .collect::<Vec<_>>();
+impl std::fmt::Display for TagCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({} open, {} done, {} overdue)",
+            self.open, self.done, self.overdue
+        )
+    }
+}
+
+fn list_tag(
+    config: &crate::Config,
+    sigil: char,
+    crate::opts::ListTag { tree, filter }: &crate::opts::ListTag,
+) -> crate::Result {
+    let today = todo_txt::date::today();
+    let words_with_sigil = WordsWithSigil::new(config, sigil)?;
+    let mut tags = std::collections::BTreeMap::<Vec<String>, TagCount>::new();
+
+    for file in config.sources() {
+        let list = crate::List::from(config, &file)?;
+
+        for task in list.iter().filter(|x| filter_term(&x.subject, filter)) {
+            let mut paths = std::collections::BTreeSet::new();
+
+            for word in words_with_sigil.find(&task.to_string()) {
+                if *tree {
+                    let segments = word
+                        .split(['.', '/'])
+                        .map(str::to_string)
+                        .collect::<Vec<_>>();
+
+                    for depth in 1..=segments.len() {
+                        paths.insert(segments[..depth].to_vec());
+                    }
+                } else {
+                    paths.insert(vec![word]);
+                }
+            }
 
-        tags.dedup();
+            for path in paths {
+                let count = tags.entry(path).or_default();
 
-        for tag in tags {
-            if filter_term(&tag, $filter) {
-                println!("{tag}");
+                if task.finished {
+                    count.done += 1;
+                } else {
+                    count.open += 1;
+
+                    if task.due_date.is_some_and(|x| x < today) {
+                        count.overdue += 1;
+                    }
+                }
             }
         }
+    }
+
+    let color = if sigil == '+' {
+        &config.colors.project
+    } else {
+        &config.colors.context
+    };
+
+    for (path, count) in tags {
+        let indent = "  ".repeat(path.len() - 1);
+        let name = path.last().unwrap();
+
+        println!("{indent}{} {count}", color.colorize(name));
+    }
+
+    Ok(())
+}
+
+/// Mirrors todo.sh `listWordsWithSigil`: strips `TODOTXT_SIGIL_BEFORE_PATTERN` and
+/// `TODOTXT_SIGIL_AFTER_PATTERN` around each word, then keeps it if the remainder matches
+/// `TODOTXT_SIGIL_VALID_PATTERN`.
+struct WordsWithSigil {
+    before: regex::Regex,
+    after: regex::Regex,
+    valid: regex::Regex,
+    word: regex::Regex,
+}
+
+impl WordsWithSigil {
+    fn new(config: &crate::Config, sigil: char) -> crate::Result<Self> {
+        let sigil = regex::escape(&sigil.to_string());
+        let regex = |pattern: String| {
+            regex::Regex::new(&pattern).map_err(|err| {
+                crate::Error::Parse(format!("Invalid sigil pattern '{pattern}': {err}"))
+            })
+        };
+
+        Ok(Self {
+            before: regex(format!("^(?:{})", config.sigil_before_pattern))?,
+            after: regex(format!("(?:{})$", config.sigil_after_pattern))?,
+            valid: regex(format!("^{sigil}(?:{})$", config.sigil_valid_pattern))?,
+            word: regex(format!("[^ ]*{sigil}[^ ]+"))?,
+        })
+    }
 
-        Ok(())
-    }};
+    fn find(&self, line: &str) -> Vec<String> {
+        self.word
+            .find_iter(line)
+            .filter_map(|x| {
+                let word = self.before.replace(x.as_str(), "");
+                let word = self.after.replace(&word, "");
+
+                if self.valid.is_match(&word) {
+                    Some(word.chars().skip(1).collect())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
 }
 
-pub(crate) fn listcon(config: &crate::Config, filter: &crate::opts::Filter) -> crate::Result {
-    list_tag!(contexts, config, filter)
+pub(crate) fn listcon(config: &crate::Config, arg: &crate::opts::ListTag) -> crate::Result {
+    list_tag(config, '@', arg)
 }
 
 pub(crate) fn listpri(
@@ -621,8 +1852,8 @@ pub(crate) fn listpri(
             return false;
         }
 
-        if let Some(p) = priority {
-            x.priority == *p
+        if let Some(priorities) = priority {
+            priorities.matches(&x.priority)
         } else {
             !x.priority.is_lowest()
         }
@@ -633,24 +1864,27 @@ pub(crate) fn listpri(
     Ok(())
 }
 
-pub(crate) fn listproj(config: &crate::Config, filter: &crate::opts::Filter) -> crate::Result {
-    list_tag!(projects, config, filter)
+pub(crate) fn listproj(config: &crate::Config, arg: &crate::opts::ListTag) -> crate::Result {
+    list_tag(config, '+', arg)
 }
 
 pub(crate) fn r#move(
     config: &crate::Config,
     crate::opts::Move { item, dest, src }: &crate::opts::Move,
 ) -> crate::Result {
+    let src_file = format!("{}/{src}", config.todo_dir);
+    let mut src_list = crate::List::from(config, &src_file)?;
+    let item = &src_list.resolve(item)?;
+    src_list.get(item)?;
+
     if !confirm(config, &format!("Move {item} form {src} to {dest}"))? {
-        return Ok(());
+        return Err(crate::Error::Cancelled);
     }
 
-    let src_file = format!("{}/{src}", config.todo_dir);
-    let mut src_list = crate::List::from(&src_file)?;
     let dest_file = format!("{}/{dest}", config.todo_dir);
-    let mut dest_list = crate::List::from(&dest_file)?;
+    let mut dest_list = crate::List::from(config, &dest_file)?;
 
-    let task = src_list.remove(*item);
+    let task = src_list.delete(*item, config.preserve_line_numbers)?;
     dest_list.push(task.clone());
 
     src_list.save()?;
@@ -664,6 +1898,43 @@ pub(crate) fn r#move(
     Ok(())
 }
 
+pub(crate) fn next(config: &crate::Config, arg: &crate::opts::Next) -> crate::Result {
+    let (count, filter) = &arg.split()?;
+    let list = crate::List::from(config, &config.todo_file)?;
+    let width = list.len().max(1).ilog10() as usize + 1;
+    let today = todo_txt::date::today();
+    let graph = list.dependencies();
+
+    let mut tasks = list
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (i + 1, x))
+        .filter(|(item, x)| {
+            !crate::is_blank(x) && filter_list(x, filter) && !graph.is_blocked(*item)
+        })
+        .map(|(item, task)| {
+            let score = config.urgency.score(task, graph.is_blocking(item), today);
+
+            (score, item, task)
+        })
+        .collect::<Vec<_>>();
+
+    tasks.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    tasks.truncate(*count);
+
+    for (score, item, task) in &tasks {
+        println!("{score:5.1} {}", print(config, width, (*item, task)));
+    }
+
+    print_summary(&[Summary {
+        file: config.todo_file.clone(),
+        total: list.count(),
+        show: tasks.len(),
+    }]);
+
+    Ok(())
+}
+
 #[cfg(feature = "extended")]
 pub(crate) fn note(config: &crate::Config, subcommand: &crate::opts::Note) -> crate::Result {
     match subcommand {
@@ -679,18 +1950,27 @@ pub(crate) fn note_add(
     config: &crate::Config,
     crate::opts::Item { item }: &crate::opts::Item,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    let todo = list.get_mut(item);
+    let todo = list.get_mut(item)?;
     todo.note = todo_txt::task::Note::Short(String::new());
-    todo.note.write()?;
+
+    if !config.dry_run {
+        todo.note.write()?;
+    }
 
     list.save()?;
 
     println!("TODO: Note added to task {item}");
 
     if !config.force && confirm(config, "Edit note?")? {
-        note_edit(config, &crate::opts::Item { item: *item })?;
+        note_edit(
+            config,
+            &crate::opts::Item {
+                item: crate::opts::TaskRef::Line(*item),
+            },
+        )?;
     }
 
     Ok(())
@@ -703,9 +1983,10 @@ pub(crate) fn note_edit(
 ) -> crate::Result {
     let editor = envir::get("EDITOR")?;
 
-    let list = crate::List::from(&config.todo_file)?;
+    let list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    let todo_txt::task::Note::Long { filename, .. } = &list.get(item).note else {
+    let todo_txt::task::Note::Long { filename, .. } = &list.get(item)?.note else {
         println!("TODO: Task {item} has no note.");
         return Ok(());
     };
@@ -722,9 +2003,10 @@ pub(crate) fn note_show(
     config: &crate::Config,
     crate::opts::Item { item }: &crate::opts::Item,
 ) -> crate::Result {
-    let list = crate::List::from(&config.todo_file)?;
+    let list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    if let Some(note) = list.get(item).note.content() {
+    if let Some(note) = list.get(item)?.note.content() {
         let note = exec(&config.note_filter, note)?;
         print!("{note}");
     } else {
@@ -748,24 +2030,26 @@ pub(crate) fn prepend(
     config: &crate::Config,
     crate::opts::Append { item, add }: &crate::opts::Append,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    let mut text = if add.task.is_empty() {
+    let text = if add.task.is_empty() {
         ask(config, "Prepend:")?
     } else {
         add.task.join(" ")
     };
+    let mut text = crate::date::resolve(&text, config.week_start);
 
     if !text.ends_with(' ') {
         text.push(' ');
     }
 
-    list.get_mut(item).subject.insert_str(0, &text);
+    list.get_mut(item)?.subject.insert_str(0, &text);
 
     list.save()?;
 
     if config.verbose > 0 {
-        println!("{item} {}", list.get(item));
+        println!("{item} {}", list.get(item)?);
     }
 
     Ok(())
@@ -775,16 +2059,17 @@ pub(crate) fn pri(
     config: &crate::Config,
     crate::opts::Pri { item, priority }: &crate::opts::Pri,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    let task = list.get_mut(item);
+    let task = list.get_mut(item)?;
     let oldpri = task.priority.clone();
     task.priority = (*priority).try_into()?;
 
     list.save()?;
 
     if config.verbose > 0 {
-        let task = list.get(item);
+        let task = list.get(item)?;
 
         println!("{item} {task}");
 
@@ -805,9 +2090,10 @@ pub(crate) fn pridown(
     config: &crate::Config,
     crate::opts::Item { item }: &crate::opts::Item,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    let task = list.get_mut(item);
+    let task = list.get_mut(item)?;
     task.priority -= 1;
 
     list.save()
@@ -817,32 +2103,106 @@ pub(crate) fn priup(
     config: &crate::Config,
     crate::opts::Item { item }: &crate::opts::Item,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
-    let task = list.get_mut(item);
+    let task = list.get_mut(item)?;
     task.priority += 1;
 
     list.save()
 }
 
+pub(crate) fn reopen(
+    config: &crate::Config,
+    crate::opts::Item { item }: &crate::opts::Item,
+) -> crate::Result {
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+
+    let (mut done, line) = match item {
+        crate::opts::TaskRef::Line(line) => {
+            let mut found = None;
+            let mut offset = 0;
+
+            // Lines are numbered through the done files one after the other, like `listall`.
+            for done_file in config.done_files() {
+                let done = crate::List::from(config, &done_file)?;
+
+                if *line <= offset + done.len() {
+                    found = Some((done, line - offset));
+                    break;
+                }
+
+                offset += done.len();
+            }
+
+            found.ok_or(crate::Error::NoTask(*line))?
+        }
+        crate::opts::TaskRef::Id(id) => {
+            let mut found = None;
+
+            for done_file in config.done_files() {
+                let done = crate::List::from(config, &done_file)?;
+
+                if let Ok(line) = done.resolve(item) {
+                    found = Some((done, line));
+                    break;
+                }
+            }
+
+            found.ok_or_else(|| crate::Error::NoTaskId(id.clone()))?
+        }
+    };
+
+    let mut task = done.remove(line)?;
+    task.uncomplete();
+
+    let successor = remove_successor(config, &mut todo, &task)?;
+
+    todo.push(task.clone());
+
+    todo.save()?;
+    done.save()?;
+
+    if config.verbose > 0 {
+        if let Some(successor) = successor {
+            println!("TODO: Removed recurring task '{successor}'.");
+        }
+
+        println!("{} {task}", todo.len());
+        println!("TODO: {item} reopened.");
+    }
+
+    Ok(())
+}
+
 pub(crate) fn replace(
     config: &crate::Config,
     crate::opts::Replace { item, text }: &crate::opts::Replace,
 ) -> crate::Result {
-    let mut list = crate::List::from(&config.todo_file)?;
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
 
     let text = match text {
         Some(text) => text.clone(),
         None => ask(config, "Replace:")?,
     };
 
-    let old_task = list.get(item).clone();
-    (*list.get_mut(item)) = text.parse()?;
+    let old_task = list.get(item)?.clone();
+    let mut new_task: crate::Task = crate::date::resolve(&text, config.week_start).parse()?;
+
+    if let Some(id) = old_task.tags.get(crate::ID_TAG) {
+        new_task
+            .tags
+            .entry(crate::ID_TAG.to_string())
+            .or_insert_with(|| id.clone());
+    }
+
+    (*list.get_mut(item)?) = new_task;
 
     list.save()?;
 
     if config.verbose > 0 {
-        let new_task = list.get(item);
+        let new_task = list.get(item)?;
 
         println!("{item} {old_task}");
         println!("TODO: Replaced task with:");
@@ -852,20 +2212,55 @@ pub(crate) fn replace(
     Ok(())
 }
 
-pub(crate) fn report(config: &crate::Config) -> crate::Result {
-    use std::io::Write;
+pub(crate) fn snooze(
+    config: &crate::Config,
+    crate::opts::Snooze { item, when }: &crate::opts::Snooze,
+) -> crate::Result {
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let item = &list.resolve(item)?;
+    let today = todo_txt::date::today();
+
+    let task = list.get_mut(item)?;
+
+    // Offsets are added to a threshold still in the future, other expressions set it.
+    let base = if when.starts_with(|c: char| c.is_ascii_digit() || c == '+') {
+        task.threshold_date.filter(|x| *x > today).unwrap_or(today)
+    } else {
+        today
+    };
+
+    let threshold = todo_txt::Date::parse_from_str(when, "%Y-%m-%d")
+        .ok()
+        .or_else(|| crate::date::parse(when, base, config.week_start))
+        .ok_or_else(|| crate::Error::Parse(format!("Invalid date: {when}")))?;
+
+    task.threshold_date = Some(threshold);
 
-    let todo = crate::List::from(&config.todo_file)?;
-    let done = crate::List::from(&config.done_file)?;
+    list.save()?;
+
+    if config.verbose > 0 {
+        println!("{item} {}", list.get(item)?);
+        println!("TODO: {item} snoozed until {threshold}.");
+    }
 
-    let mut file = std::fs::OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(&config.report_file)?;
+    Ok(())
+}
+
+pub(crate) fn report(config: &crate::Config) -> crate::Result {
+    let todo = crate::List::from(config, &config.todo_file)?;
+    let done = config
+        .done_files()
+        .iter()
+        .map(|x| crate::List::from(config, x).map(|x| x.count()))
+        .sum::<crate::Result<usize>>()?;
 
     let now = chrono::offset::Local::now();
 
-    file.write_all(format!("{} {} {}\n", now.format("%FT%X"), todo.len(), done.len()).as_bytes())?;
+    crate::append_file(
+        config,
+        &config.report_file,
+        &format!("{} {} {}\n", now.format("%FT%X"), todo.count(), done),
+    )?;
 
     if config.verbose > 0 {
         println!("TODO: Report file updated.");
@@ -874,18 +2269,102 @@ pub(crate) fn report(config: &crate::Config) -> crate::Result {
     Ok(())
 }
 
+pub(crate) fn undone(
+    config: &crate::Config,
+    crate::opts::Item { item }: &crate::opts::Item,
+) -> crate::Result {
+    let mut todo = crate::List::from(config, &config.todo_file)?;
+    let item = &todo.resolve(item)?;
+
+    let task = todo.get_mut(item)?;
+
+    if !task.finished {
+        return Err(crate::Error::NotDone(*item));
+    }
+
+    task.uncomplete();
+    let task = task.clone();
+
+    let successor = remove_successor(config, &mut todo, &task)?;
+
+    todo.save()?;
+
+    if config.verbose > 0 {
+        if let Some(successor) = successor {
+            println!("TODO: Removed recurring task '{successor}'.");
+        }
+
+        println!("{item} {task}");
+        println!("TODO: {item} marked as not done.");
+    }
+
+    Ok(())
+}
+
+pub(crate) fn wake(
+    config: &crate::Config,
+    crate::opts::Wake { items }: &crate::opts::Wake,
+) -> crate::Result {
+    let mut list = crate::List::from(config, &config.todo_file)?;
+    let today = todo_txt::date::today();
+
+    let items = if items.is_empty() {
+        list.iter()
+            .enumerate()
+            .filter(|(_, x)| !x.finished && x.threshold_date.is_some_and(|x| x <= today))
+            .map(|(i, _)| i + 1)
+            .collect()
+    } else {
+        items
+            .iter()
+            .map(|x| list.resolve(x))
+            .collect::<crate::Result<Vec<_>>>()?
+    };
+
+    for item in &items {
+        list.get_mut(item)?.threshold_date = None;
+    }
+
+    list.save()?;
+
+    if config.verbose > 0 {
+        for item in &items {
+            println!("{item} {}", list.get(item)?);
+            println!("TODO: {item} woken up.");
+        }
+    }
+
+    Ok(())
+}
+
 pub(crate) fn external(config: &crate::Config, args: &[String]) -> crate::Result {
-    use anyhow::Context;
+    // An add-on writes the files itself, it can't be previewed.
+    if config.dry_run {
+        return Err(crate::Error::Usage(format!(
+            "{} does not support --dry-run",
+            args[0]
+        )));
+    }
 
     let command = format!("{}/{}", config.action_dir, args[0]);
 
-    std::process::Command::new(&command)
+    let output = std::process::Command::new(&command)
         .args(args)
         .stdin(std::process::Stdio::inherit())
         .stdout(std::process::Stdio::inherit())
         .stderr(std::process::Stdio::inherit())
         .output()
-        .with_context(|| format!("Unable to execute {command}"))?;
+        .map_err(|source| crate::Error::Addon {
+            command: command.clone(),
+            source,
+        })?;
+
+    if !output.status.success() {
+        return Err(crate::Error::AddonFailed {
+            command,
+            status: output.status.code().unwrap_or(-1),
+        });
+    }
 
     Ok(())
 }
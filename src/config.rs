@@ -3,17 +3,193 @@
 pub struct Config {
     #[envir(nested)]
     inner: todo_txt::Config,
+    #[envir(default = "30")]
+    pub agenda_horizon: u64,
+    #[envir(default)]
+    pub archive_rotation: Rotation,
+    #[envir(default)]
+    pub auto_escalate: bool,
+    #[envir(skip)]
+    pub dry_run: bool,
+    #[envir(default = "1:A,3:B")]
+    pub escalation: Escalation,
+    #[envir(default)]
+    pub id_on_add: bool,
     #[envir(default = "true")]
     pub reldate: bool,
     #[envir(default = "14")]
     pub reldate_dayrange: usize,
+    #[envir(default)]
+    pub show_ids: bool,
+    #[envir(default)]
+    pub sigil_after_pattern: String,
+    #[envir(default)]
+    pub sigil_before_pattern: String,
+    #[envir(default = ".*")]
+    pub sigil_valid_pattern: String,
+    pub sourcevar: Option<String>,
+    #[envir(default)]
+    pub urgency: crate::urgency::Weights,
+    #[envir(default = "monday")]
+    pub week_start: chrono::Weekday,
 }
 
 impl Config {
-    pub fn from_env() -> Self {
+    pub fn from_env() -> crate::Result<Self> {
         todo_txt::Config::load_env();
 
-        envir::from_env().unwrap()
+        envir::from_env().map_err(Into::into)
+    }
+
+    /// Files read by `listcon` and `listproj`. `TODOTXT_SOURCEVAR` accepts the todo.sh forms
+    /// `$DONE_FILE` or `("$TODO_FILE" "$DONE_FILE")`.
+    pub fn sources(&self) -> Vec<String> {
+        static VAR_REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+            regex::Regex::new(r"\$(\{(?P<braced>\w+)\}|(?P<name>\w+))").unwrap()
+        });
+
+        let Some(sourcevar) = &self.sourcevar else {
+            return vec![self.todo_file.clone()];
+        };
+
+        let sources = sourcevar
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split_whitespace()
+            .map(|x| x.trim_matches(|c| c == '"' || c == '\''))
+            .map(|x| {
+                VAR_REGEX
+                    .replace_all(x, |caps: &regex::Captures| {
+                        let name = caps.name("braced").or(caps.name("name")).unwrap();
+
+                        match name.as_str() {
+                            "TODO_DIR" => self.todo_dir.clone(),
+                            "TODO_FILE" => self.todo_file.clone(),
+                            "DONE_FILE" => self.done_file.clone(),
+                            "REPORT_FILE" => self.report_file.clone(),
+                            name => envir::get(name).unwrap_or_default(),
+                        }
+                    })
+                    .to_string()
+            })
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>();
+
+        if sources.is_empty() {
+            vec![self.todo_file.clone()]
+        } else {
+            sources
+        }
+    }
+
+    /// Archive receiving a task finished on `date`: `done.txt`, `done-2026.txt` or
+    /// `done-2026-10.txt` depending on `TODOTXT_ARCHIVE_ROTATION`.
+    pub fn archive_file(&self, date: todo_txt::Date) -> String {
+        let stem = self.done_file.trim_end_matches(".txt");
+
+        match self.archive_rotation {
+            Rotation::None => self.done_file.clone(),
+            Rotation::Year => format!("{stem}-{}.txt", date.format("%Y")),
+            Rotation::Month => format!("{stem}-{}.txt", date.format("%Y-%m")),
+        }
+    }
+
+    /// `done.txt` followed by every rotated archive, oldest first.
+    pub fn done_files(&self) -> Vec<String> {
+        static ROTATED_REGEX: std::sync::LazyLock<regex::Regex> =
+            std::sync::LazyLock::new(|| regex::Regex::new(r"^-\d{4}(-\d{2})?\.txt$").unwrap());
+
+        let done_file = std::path::Path::new(&self.done_file);
+        let stem = done_file
+            .file_stem()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let dir = done_file
+            .parent()
+            .filter(|x| !x.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."));
+
+        let mut rotated = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+
+                name.strip_prefix(&stem)
+                    .is_some_and(|x| ROTATED_REGEX.is_match(x))
+            })
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        rotated.sort_by(|a, b| a.trim_end_matches(".txt").cmp(b.trim_end_matches(".txt")));
+        rotated.insert(0, self.done_file.clone());
+
+        rotated
+    }
+}
+
+/// Period covered by each archive file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Year,
+    Month,
+}
+
+impl std::str::FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "none" => Ok(Self::None),
+            "year" | "yearly" => Ok(Self::Year),
+            "month" | "monthly" => Ok(Self::Month),
+            _ => Err(format!("Invalid archive rotation: {s}")),
+        }
+    }
+}
+
+/// Priorities given to open tasks as their due date approaches, parsed from `DAYS:PRIORITY`
+/// pairs like `1:A,3:B`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Escalation(Vec<(i64, todo_txt::Priority)>);
+
+impl Escalation {
+    /// Highest priority reached by a task due in `days` (negative when overdue).
+    pub fn priority(&self, days: i64) -> Option<todo_txt::Priority> {
+        self.0
+            .iter()
+            .filter(|(limit, _)| days <= *limit)
+            .map(|(_, priority)| priority.clone())
+            .max()
+    }
+}
+
+impl std::str::FromStr for Escalation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Vec::new();
+
+        for rule in s.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let invalid = || format!("Invalid escalation rule: {rule}");
+
+            let (days, priority) = rule.split_once(':').ok_or_else(invalid)?;
+            let days = days.trim().parse().map_err(|_| invalid())?;
+
+            let mut chars = priority.trim().chars();
+            let priority = match (chars.next(), chars.next()) {
+                (Some(c), None) => todo_txt::Priority::try_from(c).map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            };
+
+            rules.push((days, priority));
+        }
+
+        Ok(Self(rules))
     }
 }
 
@@ -25,18 +201,25 @@ impl std::ops::Deref for Config {
     }
 }
 
-impl From<&crate::Opt> for Config {
-    fn from(value: &crate::Opt) -> Self {
-        let mut config = Self::from_env();
+impl TryFrom<&crate::Opt> for Config {
+    type Error = crate::Error;
+
+    fn try_from(value: &crate::Opt) -> crate::Result<Self> {
+        let mut config = Self::from_env()?;
 
         config.inner.auto_archive |= !value.dont_auto_archive;
         config.inner.date_on_add |= value.append_current_date;
         config.inner.disable_filter |= value.disable_final_filter;
+        config.dry_run |= value.dry_run;
         config.inner.force |= value.force;
         config.inner.plain |= value.plain_text;
-        config.inner.preserve_line_numbers |= !value.dont_preserve_line_numbers;
+        config.inner.preserve_line_numbers &= !value.dont_preserve_line_numbers;
         config.inner.verbose |= value.verbose;
 
-        config
+        if let Some(crate::opts::Command::List(list)) = &value.command {
+            config.show_ids |= list.ids;
+        }
+
+        Ok(config)
     }
 }
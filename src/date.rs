@@ -0,0 +1,120 @@
+//! Natural and relative dates accepted in `due:` and `t:` tags by add, append, prepend and
+//! replace.
+//!
+//! An expression is one of:
+//!
+//! - `today`, `tomorrow` or `yesterday`;
+//! - a weekday (`mon`, `friday`…): its next occurrence, a week later if it is today;
+//! - `+N` or `-N` followed by `d`, `w`, `m` or `y`: N days, weeks, months or years from today;
+//! - `next-week`, `next-month` or `next-year`: the first day of the next period;
+//! - `eow`, `eom` or `eoy`: the last day of the current week, month or year.
+//!
+//! Weeks start on `TODOTXT_WEEK_START` (monday by default). ISO dates and unknown expressions are
+//! left untouched.
+
+use chrono::Datelike as _;
+
+/// Tags whose value is a date.
+const DATE_TAGS: &[&str] = &["due", "t"];
+
+/// Replaces natural date expressions in the date tags of `text` by ISO dates.
+pub fn resolve(text: &str, week_start: chrono::Weekday) -> String {
+    static REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+        regex::Regex::new(r"(?P<space>^|\s)(?P<key>[\w-]+):(?P<expr>\S+)").unwrap()
+    });
+
+    let today = todo_txt::date::today();
+
+    REGEX
+        .replace_all(text, |caps: &regex::Captures| {
+            let key = &caps["key"];
+
+            match parse(&caps["expr"], today, week_start) {
+                Some(date) if DATE_TAGS.contains(&key) => {
+                    format!("{}{key}:{}", &caps["space"], date.format("%Y-%m-%d"))
+                }
+                _ => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Date designated by `expr`, relative to `today`.
+pub fn parse(
+    expr: &str,
+    today: todo_txt::Date,
+    week_start: chrono::Weekday,
+) -> Option<todo_txt::Date> {
+    let expr = expr.to_lowercase();
+
+    let date = match expr.as_str() {
+        "today" => today,
+        "tomorrow" => today.succ_opt()?,
+        "yesterday" => today.pred_opt()?,
+        "next-week" => start_of_week(today, week_start) + chrono::Days::new(7),
+        "next-month" => start_of_month(today).checked_add_months(chrono::Months::new(1))?,
+        "next-year" => todo_txt::Date::from_ymd_opt(today.year() + 1, 1, 1)?,
+        "eow" => start_of_week(today, week_start) + chrono::Days::new(6),
+        "eom" => end_of_month(today),
+        "eoy" => todo_txt::Date::from_ymd_opt(today.year(), 12, 31)?,
+        expr => {
+            if let Ok(weekday) = expr.parse::<chrono::Weekday>() {
+                let days = (weekday.days_since(today.weekday()) + 6) % 7 + 1;
+
+                today + chrono::Days::new(days as u64)
+            } else {
+                offset(expr, today)?
+            }
+        }
+    };
+
+    Some(date)
+}
+
+/// `+3d`, `-2w`, `+1m`, `1y`…
+fn offset(expr: &str, today: todo_txt::Date) -> Option<todo_txt::Date> {
+    let (negative, expr) = match expr.as_bytes().first()? {
+        b'+' => (false, &expr[1..]),
+        b'-' => (true, &expr[1..]),
+        _ => (false, expr),
+    };
+
+    let unit = expr.chars().last()?;
+    let n = expr[..expr.len() - unit.len_utf8()].parse::<u32>().ok()?;
+
+    let months = match unit {
+        'd' => return shift_days(today, n as u64, negative),
+        'w' => return shift_days(today, n as u64 * 7, negative),
+        'm' => n,
+        'y' => n * 12,
+        _ => return None,
+    };
+
+    if negative {
+        today.checked_sub_months(chrono::Months::new(months))
+    } else {
+        today.checked_add_months(chrono::Months::new(months))
+    }
+}
+
+fn shift_days(today: todo_txt::Date, days: u64, negative: bool) -> Option<todo_txt::Date> {
+    if negative {
+        today.checked_sub_days(chrono::Days::new(days))
+    } else {
+        today.checked_add_days(chrono::Days::new(days))
+    }
+}
+
+pub fn start_of_week(date: todo_txt::Date, week_start: chrono::Weekday) -> todo_txt::Date {
+    date - chrono::Days::new(date.weekday().days_since(week_start) as u64)
+}
+
+pub fn start_of_month(date: todo_txt::Date) -> todo_txt::Date {
+    date.with_day(1).unwrap()
+}
+
+pub fn end_of_month(date: todo_txt::Date) -> todo_txt::Date {
+    let days = todo_txt::task::Period::days_in_month(date.month(), date.year());
+
+    date.with_day(days).unwrap()
+}
@@ -0,0 +1,124 @@
+/// Exit codes returned by the binary, documented in `help`.
+pub const EXIT_CODES: &[(u8, &str)] = &[
+    (0, "success"),
+    (1, "no such task or term"),
+    (
+        2,
+        "invalid arguments, task, priority, pattern or configuration",
+    ),
+    (3, "unable to read or write a file"),
+    (4, "the todo directory is locked by another process"),
+    (5, "an add-on failed or could not be started"),
+    (6, "cancelled by the user"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("TODO: Unable to execute {command}: {source}")]
+    Addon {
+        command: String,
+        source: std::io::Error,
+    },
+    #[error("TODO: {command} exited with status {status}")]
+    AddonFailed { command: String, status: i32 },
+    #[error("TODO: Cancelled.")]
+    Cancelled,
+    #[error("TODO: {0}")]
+    Config(#[from] envir::Error),
+    #[error("TODO: Failed to {action} '{path}' file: {source}")]
+    File {
+        action: &'static str,
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("TODO: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TODO: '{0}' is locked by another process.")]
+    Locked(String),
+    #[error("TODO: No task {0}.")]
+    NoTask(usize),
+    #[error("TODO: No task #{0}.")]
+    NoTaskId(String),
+    #[error("TODO: {0} is not marked as done.")]
+    NotDone(usize),
+    #[error("TODO: {0}")]
+    Parse(String),
+    #[error("TODO: '{0}' not found; no removal done.")]
+    TermNotFound(String),
+    #[error("TODO: {0}")]
+    Todo(#[from] todo_txt::Error),
+    #[error("TODO: {0}")]
+    Usage(String),
+}
+
+impl Error {
+    pub fn file(action: &'static str, path: &str) -> impl FnOnce(std::io::Error) -> Self {
+        move |source| Self::File {
+            action,
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::NoTask(_) | Self::NoTaskId(_) | Self::TermNotFound(_) => 1,
+            Self::File { .. } | Self::Io(_) | Self::Todo(todo_txt::Error::Note(_)) => 3,
+            Self::Config(_)
+            | Self::NotDone(_)
+            | Self::Parse(_)
+            | Self::Todo(_)
+            | Self::Usage(_) => 2,
+            Self::Locked(_) => 4,
+            Self::Addon { .. } | Self::AddonFailed { .. } => 5,
+            Self::Cancelled => 6,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Addon { .. } | Self::AddonFailed { .. } => "addon",
+            Self::Cancelled => "cancelled",
+            Self::Config(_) => "config",
+            Self::File { .. } | Self::Io(_) => "io",
+            Self::Locked(_) => "locked",
+            Self::NoTask(_) | Self::NoTaskId(_) => "no_task",
+            Self::NotDone(_) => "not_done",
+            Self::Parse(_) | Self::Todo(_) => "parse",
+            Self::TermNotFound(_) => "term_not_found",
+            Self::Usage(_) => "usage",
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut message = String::new();
+
+        for c in self.to_string().chars() {
+            match c {
+                '"' => message.push_str("\\\""),
+                '\\' => message.push_str("\\\\"),
+                '\n' => message.push_str("\\n"),
+                c if c.is_control() => message.push_str(&format!("\\u{:04x}", c as u32)),
+                c => message.push(c),
+            }
+        }
+
+        format!(
+            r#"{{"error":"{}","code":{},"message":"{message}"}}"#,
+            self.kind(),
+            self.code()
+        )
+    }
+}
+
+impl From<std::convert::Infallible> for Error {
+    fn from(value: std::convert::Infallible) -> Self {
+        match value {}
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(value: regex::Error) -> Self {
+        Self::Parse(value.to_string())
+    }
+}
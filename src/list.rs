@@ -1,32 +1,335 @@
-use anyhow::Context;
+pub const ID_TAG: &str = "id";
+pub const DEP_TAG: &str = "dep";
+pub const BLOCKS_TAG: &str = "blocks";
+pub const PARENT_TAG: &str = "parent";
 
 pub struct List {
     filename: String,
+    dry_run: bool,
     inner: todo_txt::task::List<crate::Task>,
 }
 
 impl List {
-    pub fn from(filename: &str) -> crate::Result<crate::List> {
-        if !std::path::Path::new(filename).exists() {
-            std::fs::File::create(filename)
-                .with_context(|| format!("Failed to create '{filename}' file"))?;
+    pub fn from(config: &crate::Config, filename: &str) -> crate::Result<crate::List> {
+        let exists = std::path::Path::new(filename).exists();
+
+        if !exists && !config.dry_run {
+            std::fs::File::create(filename).map_err(crate::Error::file("create", filename))?;
         }
 
-        let contents = std::fs::read_to_string(filename)
-            .with_context(|| format!("Failed to read '{filename}' file"))?;
+        let contents = if exists || !config.dry_run {
+            std::fs::read_to_string(filename).map_err(crate::Error::file("read", filename))?
+        } else {
+            String::new()
+        };
+
+        // Blank lines are kept as empty tasks so that ITEM# always is the line number.
+        let inner = contents
+            .lines()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    crate::Task::default()
+                } else {
+                    line.to_string().into()
+                }
+            })
+            .collect();
 
         let list = Self {
             filename: filename.to_string(),
-            inner: todo_txt::task::List::from(&contents),
+            dry_run: config.dry_run,
+            inner,
         };
 
         Ok(list)
     }
 
     pub fn save(&self) -> crate::Result {
-        std::fs::write(&self.filename, self.inner.to_string().as_bytes())
-            .with_context(|| format!("Failed to save in '{}' file", self.filename))
+        write_file(&self.filename, &self.inner.to_string(), self.dry_run)
+    }
+
+    pub fn get(&self, item: &usize) -> crate::Result<&crate::Task> {
+        self.check(*item)?;
+
+        Ok(self.inner.get(item))
+    }
+
+    pub fn get_mut(&mut self, item: &usize) -> crate::Result<&mut crate::Task> {
+        self.check(*item)?;
+
+        Ok(self.inner.get_mut(item))
+    }
+
+    pub fn remove(&mut self, item: usize) -> crate::Result<crate::Task> {
+        self.check(item)?;
+
+        Ok(self.inner.remove(item))
+    }
+
+    /// Line number of the task designated by ITEM# or #ID.
+    pub fn resolve(&self, item: &crate::opts::TaskRef) -> crate::Result<usize> {
+        use crate::opts::TaskRef;
+
+        match item {
+            TaskRef::Line(line) => Ok(*line),
+            TaskRef::Id(id) => self
+                .iter()
+                .position(|x| x.tags.get(ID_TAG) == Some(id))
+                .map(|x| x + 1)
+                .ok_or_else(|| crate::Error::NoTaskId(id.clone())),
+        }
+    }
+
+    /// Generates a short id not used by any task of the list.
+    pub fn new_id(&self) -> String {
+        use std::hash::{BuildHasher as _, Hasher as _};
+
+        loop {
+            let mut hasher = std::hash::RandomState::new().build_hasher();
+            hasher.write_usize(self.len());
+            let mut seed = hasher.finish();
+
+            let id = (0..4)
+                .map(|_| {
+                    let c = char::from_digit((seed % 36) as u32, 36).unwrap();
+                    seed /= 36;
+                    c
+                })
+                .collect::<String>();
+
+            if !self.iter().any(|x| x.tags.get(ID_TAG) == Some(&id)) {
+                return id;
+            }
+        }
+    }
+
+    /// Removes the task, leaving a blank line in its place when line numbers are preserved.
+    pub fn delete(
+        &mut self,
+        item: usize,
+        preserve_line_numbers: bool,
+    ) -> crate::Result<crate::Task> {
+        if preserve_line_numbers {
+            Ok(std::mem::take(self.get_mut(&item)?))
+        } else {
+            self.remove(item)
+        }
+    }
+
+    /// Dependencies between the tasks, resolved in one pass over the list.
+    pub fn dependencies(&self) -> Dependencies {
+        let mut graph = Dependencies::default();
+
+        if !self
+            .iter()
+            .any(|x| x.tags.contains_key(DEP_TAG) || x.tags.contains_key(BLOCKS_TAG))
+        {
+            return graph;
+        }
+
+        let mut lines = std::collections::HashMap::<&str, Vec<usize>>::new();
+
+        for (item, task) in self.iter().enumerate().map(|(i, x)| (i + 1, x)) {
+            if let Some(id) = task.tags.get(ID_TAG) {
+                lines.entry(id).or_default().push(item);
+            }
+
+            if !task.finished {
+                graph.open.insert(item);
+            }
+        }
+
+        for (item, task) in self.iter().enumerate().map(|(i, x)| (i + 1, x)) {
+            for dep in ids(task, DEP_TAG).iter().filter_map(|x| lines.get(x)) {
+                for dep in dep.iter().filter(|x| **x != item) {
+                    graph.add(item, *dep);
+                }
+            }
+
+            for dependent in ids(task, BLOCKS_TAG).iter().filter_map(|x| lines.get(x)) {
+                for dependent in dependent.iter().filter(|x| **x != item) {
+                    graph.add(*dependent, item);
+                }
+            }
+        }
+
+        for lines in graph
+            .dependencies
+            .values_mut()
+            .chain(graph.dependents.values_mut())
+        {
+            lines.sort_unstable();
+            lines.dedup();
+        }
+
+        graph
+    }
+
+    /// Line of the task designated by the `parent:` tag of `item`.
+    pub fn parent(&self, item: usize) -> Option<usize> {
+        let id = self.get(&item).ok()?.tags.get(PARENT_TAG)?;
+
+        self.iter()
+            .position(|x| x.tags.get(ID_TAG) == Some(id))
+            .map(|x| x + 1)
+            .filter(|x| *x != item)
+    }
+
+    /// Lines of the subtasks of `item`.
+    pub fn children(&self, item: usize) -> Vec<usize> {
+        let Some(id) = self.get(&item).ok().and_then(|x| x.tags.get(ID_TAG)) else {
+            return Vec::new();
+        };
+
+        self.iter()
+            .enumerate()
+            .filter(|(i, x)| *i + 1 != item && x.tags.get(PARENT_TAG) == Some(id))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+
+    /// Topmost ancestor of `item`, stopping before a `parent:` cycle.
+    pub fn root(&self, item: usize) -> usize {
+        let mut path = vec![item];
+
+        while let Some(parent) = self.parent(*path.last().unwrap()) {
+            if path.contains(&parent) {
+                break;
+            }
+
+            path.push(parent);
+        }
+
+        path.last().copied().unwrap_or(item)
     }
+
+    /// `item` followed by all its descendants, depth first.
+    pub fn family(&self, item: usize) -> Vec<usize> {
+        let mut family = vec![item];
+        let mut i = 0;
+
+        while i < family.len() {
+            let children = self
+                .children(family[i])
+                .into_iter()
+                .filter(|x| !family.contains(x))
+                .collect::<Vec<_>>();
+
+            family.splice(i + 1..i + 1, children);
+            i += 1;
+        }
+
+        family
+    }
+
+    /// Number of tasks, blank lines excluded.
+    pub fn count(&self) -> usize {
+        self.iter().filter(|x| !is_blank(x)).count()
+    }
+
+    fn check(&self, item: usize) -> crate::Result {
+        if item == 0 || item > self.len() || is_blank(self.inner.get(&item)) {
+            return Err(crate::Error::NoTask(item));
+        }
+
+        Ok(())
+    }
+}
+
+/// Under `--dry-run`, files are diffed on stdout instead of written.
+fn write_file(filename: &str, contents: &str, dry_run: bool) -> crate::Result {
+    if dry_run {
+        print_diff(filename, contents);
+        return Ok(());
+    }
+
+    std::fs::write(filename, contents.as_bytes()).map_err(crate::Error::file("save in", filename))
+}
+
+pub fn append_file(config: &crate::Config, filename: &str, contents: &str) -> crate::Result {
+    use std::io::Write as _;
+
+    if config.dry_run {
+        let old = std::fs::read_to_string(filename).unwrap_or_default();
+        print_diff(filename, &format!("{old}{contents}"));
+        return Ok(());
+    }
+
+    std::fs::File::options()
+        .append(true)
+        .create(true)
+        .open(filename)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(crate::Error::file("append to", filename))
+}
+
+/// Prints the unified diff between the current content of `filename` and `contents`.
+fn print_diff(filename: &str, contents: &str) {
+    let old = std::fs::read_to_string(filename).unwrap_or_default();
+    let old = old.lines().collect::<Vec<_>>();
+    let new = contents.lines().collect::<Vec<_>>();
+
+    let diff = difflib::unified_diff(&old, &new, filename, filename, "", "", 3);
+
+    if diff.is_empty() {
+        return;
+    }
+
+    println!("--- {filename}");
+    println!("+++ {filename}");
+
+    // difflib headers carry an empty date, they are replaced by the two lines above.
+    for line in diff.iter().skip(2) {
+        println!("{}", line.trim_end_matches('\n'));
+    }
+}
+
+/// Lines linked by `dep:` and `blocks:` tags, see [`List::dependencies`].
+#[derive(Default)]
+pub struct Dependencies {
+    dependencies: std::collections::HashMap<usize, Vec<usize>>,
+    dependents: std::collections::HashMap<usize, Vec<usize>>,
+    open: std::collections::HashSet<usize>,
+}
+
+impl Dependencies {
+    /// Lines of the tasks that `item` depends on: listed in its `dep:` tag, or listing its id in
+    /// their `blocks:` tag.
+    pub fn of(&self, item: usize) -> &[usize] {
+        self.dependencies.get(&item).map_or(&[], Vec::as_slice)
+    }
+
+    /// Lines of the tasks depending on `item`.
+    pub fn dependents(&self, item: usize) -> &[usize] {
+        self.dependents.get(&item).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether an open task depends on `item`.
+    pub fn is_blocking(&self, item: usize) -> bool {
+        self.dependents(item).iter().any(|x| self.open.contains(x))
+    }
+
+    /// Whether one of the dependencies of `item` is still open.
+    pub fn is_blocked(&self, item: usize) -> bool {
+        self.of(item).iter().any(|x| self.open.contains(x))
+    }
+
+    fn add(&mut self, item: usize, dependency: usize) {
+        self.dependencies.entry(item).or_default().push(dependency);
+        self.dependents.entry(dependency).or_default().push(item);
+    }
+}
+
+/// Comma separated ids of the `tag` of `task`.
+fn ids<'a>(task: &'a crate::Task, tag: &str) -> Vec<&'a str> {
+    task.tags
+        .get(tag)
+        .map(|x| x.split(',').collect())
+        .unwrap_or_default()
+}
+
+pub fn is_blank(task: &crate::Task) -> bool {
+    *task == crate::Task::default()
 }
 
 impl std::ops::Deref for List {
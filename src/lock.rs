@@ -0,0 +1,37 @@
+/// Exclusive lock on the todo directory, held while a command modifies files.
+///
+/// This is an advisory `flock` on `.todo.lock`, released by the OS when the process exits, even
+/// when it is interrupted. The file is left in place and only records the pid of the last holder.
+pub struct Lock {
+    _file: std::fs::File,
+}
+
+impl Lock {
+    pub fn acquire(config: &crate::Config) -> crate::Result<Self> {
+        use std::io::Write as _;
+
+        let path = std::path::Path::new(&config.todo_dir).join(".todo.lock");
+        let filename = path.display().to_string();
+
+        let mut file = std::fs::File::options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(crate::Error::file("create", &filename))?;
+
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(std::fs::TryLockError::WouldBlock) => return Err(crate::Error::Locked(filename)),
+            Err(std::fs::TryLockError::Error(err)) => {
+                return Err(crate::Error::file("lock", &filename)(err));
+            }
+        }
+
+        file.set_len(0)
+            .and_then(|()| writeln!(file, "{}", std::process::id()))
+            .map_err(crate::Error::file("write", &filename))?;
+
+        Ok(Self { _file: file })
+    }
+}
@@ -2,11 +2,18 @@
 
 mod commands;
 mod config;
+mod date;
+mod errors;
 mod list;
+mod lock;
 mod opts;
+mod recurrence;
+mod urgency;
 
 use config::Config;
+use errors::Error;
 use list::*;
+use lock::Lock;
 use opts::Opt;
 
 #[cfg(not(feature = "extended"))]
@@ -14,17 +21,65 @@ pub(crate) type Task = todo_txt::Task;
 #[cfg(feature = "extended")]
 pub(crate) type Task = todo_txt::task::Extended;
 
-pub type Result<T = ()> = anyhow::Result<T>;
+pub type Result<T = ()> = std::result::Result<T, Error>;
 
-fn main() -> Result {
+fn main() -> std::process::ExitCode {
     use clap::Parser;
-    use envir::Serialize;
-    use opts::Command::*;
 
-    let Ok(mut opt) = Opt::try_parse() else {
-        return help(&Config::from_env());
+    clap_complete::CompleteEnv::with_factory(<Opt as clap::CommandFactory>::command).complete();
+
+    let (format, result) = match Opt::try_parse() {
+        Ok(opt) => (opt.format, run(opt)),
+        Err(err) => {
+            let format = opts::Format::from_args(std::env::args());
+
+            (format, usage(&err, format))
+        }
     };
 
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            match format {
+                opts::Format::Text => eprintln!("{err}"),
+                opts::Format::Json => eprintln!("{}", err.to_json()),
+            }
+
+            std::process::ExitCode::from(err.code())
+        }
+    }
+}
+
+fn usage(err: &clap::Error, format: opts::Format) -> Result {
+    use clap::error::ErrorKind;
+
+    let display = matches!(
+        err.kind(),
+        ErrorKind::DisplayHelp | ErrorKind::DisplayVersion
+    );
+
+    // The help would garble the JSON output of a usage error.
+    if display || matches!(format, opts::Format::Text) {
+        help(&Config::from_env()?)?;
+    }
+
+    if display {
+        return Ok(());
+    }
+
+    let message = err.render().to_string();
+    let message = message.lines().next().unwrap_or_default();
+
+    Err(Error::Usage(
+        message.trim_start_matches("error: ").to_string(),
+    ))
+}
+
+fn run(mut opt: Opt) -> Result {
+    use clap::Parser;
+    use envir::Serialize;
+    use opts::Command::*;
+
     if opt.color {
         envir::set("FORCE_COLOR", true);
     }
@@ -33,7 +88,7 @@ fn main() -> Result {
         envir::set("NO_COLOR", true);
     }
 
-    let config = Config::from(&opt);
+    let config = Config::try_from(&opt)?;
 
     if opt.command.is_none() {
         let mut args = std::env::args_os().collect::<Vec<_>>();
@@ -47,42 +102,71 @@ fn main() -> Result {
     // @TODO create bash function for action
 
     if let Some(command) = opt.command {
+        let escalates = config.auto_escalate && matches!(command, List(_));
+
+        let _lock = if (command.is_mutating() || escalates) && !config.dry_run {
+            Some(Lock::acquire(&config)?)
+        } else {
+            None
+        };
+
         match command {
             Add(arg) => commands::add(&config, &arg),
             Addm(arg) => commands::addm(&config, &arg),
             Addto(arg) => commands::addto(&config, &arg),
+            Agenda(arg) => commands::agenda(&config, &arg),
             Append(arg) => commands::append(&config, &arg),
-            Archive => commands::archive(&config),
-            Deduplicate => commands::deduplicate(&config),
+            Archive(arg) => commands::archive(&config, &arg),
+            Cal(arg) => commands::cal(&config, &arg),
+            Deduplicate(arg) => commands::deduplicate(&config, &arg),
             Del(arg) => commands::del(&config, &arg),
+            Deps(arg) => commands::deps(&config, &arg),
             Delpri(arg) => commands::delpri(&config, &arg),
             Done(arg) => commands::done(&config, &arg),
             #[cfg(feature = "extended")]
             Env => commands::env(&config),
+            Escalate => commands::escalate(&config),
+            #[cfg(feature = "extended")]
             Flag(arg) => {
-                if let Some(item) = arg.item {
-                    commands::flag(&config, item)
+                if arg.items.is_empty() {
+                    commands::listflag(&config, &opts::Filter::default())
                 } else {
-                    commands::listflag(&config)
+                    commands::flag(&config, &arg.items, None)
                 }
             }
+            #[cfg(feature = "extended")]
+            Habits(arg) => commands::habits(&config, &arg),
             Help => help(&config),
+            #[cfg(feature = "extended")]
+            Hide(arg) => commands::hide(&config, &arg.items, true),
             List(arg) => commands::list(&config, &arg),
             Listall(arg) => commands::listall(&config, &arg),
             Listaddons => commands::listaddons(&config),
             Listfile(arg) => commands::listfile(&config, &arg),
+            #[cfg(feature = "extended")]
+            Listflag(arg) => commands::listflag(&config, &arg),
             Listcon(arg) => commands::listcon(&config, &arg),
             Listpri(arg) => commands::listpri(&config, &arg),
             Listproj(arg) => commands::listproj(&config, &arg),
+            Listtags(arg) => commands::listtags(&config, &arg),
             Move(arg) => commands::r#move(&config, &arg),
             #[cfg(feature = "extended")]
             Note(arg) => commands::note(&config, &arg),
+            Next(arg) => commands::next(&config, &arg),
             Prepend(arg) => commands::prepend(&config, &arg),
             Pri(arg) => commands::pri(&config, &arg),
             Pridown(arg) => commands::pridown(&config, &arg),
             Priup(arg) => commands::priup(&config, &arg),
+            Reopen(arg) => commands::reopen(&config, &arg),
             Replace(arg) => commands::replace(&config, &arg),
             Report => commands::report(&config),
+            Snooze(arg) => commands::snooze(&config, &arg),
+            Undone(arg) => commands::undone(&config, &arg),
+            #[cfg(feature = "extended")]
+            Unflag(arg) => commands::flag(&config, &arg.items, Some(false)),
+            #[cfg(feature = "extended")]
+            Unhide(arg) => commands::hide(&config, &arg.items, false),
+            Wake(arg) => commands::wake(&config, &arg),
             External(arg) => commands::external(&config, &arg),
         }
     } else {
@@ -100,13 +184,22 @@ fn help(config: &Config) -> Result {
         println!(
             "
 \x1B[0;33mENVIRONMENT:\x1B[0m
+    \x1B[0;32mCOMPLETE=bash|zsh|fish\x1B[0m          print the completion script: source <(COMPLETE=bash todo-txt-cli)
+    \x1B[0;32mTODOTXT_AGENDA_HORIZON=30\x1B[0m      number of days displayed by agenda
     \x1B[0;32mTODOTXT_AUTO_ARCHIVE\x1B[0m            is same as option -a (0)/-A (1)
+    \x1B[0;32mTODOTXT_AUTO_ESCALATE=1\x1B[0m         apply TODOTXT_ESCALATION on add, done and list
+    \x1B[0;32mTODOTXT_ESCALATION=1:A,3:B\x1B[0m      priority of tasks due within DAYS:PRIORITY
+    \x1B[0;32mTODOTXT_ARCHIVE_ROTATION=year\x1B[0m   archive into done-YYYY.txt (year) or done-YYYY-MM.txt (month)
     \x1B[0;32mTODOTXT_CFG_FILE=CONFIG_FILE\x1B[0m    is same as option -d CONFIG_FILE
     \x1B[0;32mTODOTXT_FORCE=1\x1B[0m                 is same as option -f
     \x1B[0;32mTODOTXT_PRESERVE_LINE_NUMBERS\x1B[0m   is same as option -n (0)/-N (1)
     \x1B[0;32mTODOTXT_PLAIN\x1B[0m                   is same as option -p (1)/-c (0)
     \x1B[0;32mTODOTXT_DATE_ON_ADD\x1B[0m             is same as option -t (1)/-T (0)
     \x1B[0;32mTODOTXT_PRIORITY_ON_ADD=pri\x1B[0m     default priority A-Z
+    \x1B[0;32mTODOTXT_ID_ON_ADD=1\x1B[0m             add an id:ID tag to new tasks, usable as #ID for ITEM#
+    \x1B[0;32mTODOTXT_SHOW_IDS=1\x1B[0m              is same as list --ids
+    \x1B[0;32mTODOTXT_URGENCY=due:12,+work:3\x1B[0m  weights of the urgency factors used by next
+    \x1B[0;32mTODOTXT_WEEK_START=monday\x1B[0m     first day of the week for eow, next-week and weekdays
     \x1B[0;32mTODOTXT_VERBOSE=1\x1B[0m               is same as option -v
     \x1B[0;32mTODOTXT_DISABLE_FILTER=1\x1B[0m        is same as option -x
     \x1B[0;32mTODOTXT_DEFAULT_ACTION=\"\"\x1B[0m       run this when called with no arguments
@@ -119,6 +212,12 @@ fn help(config: &Config) -> Result {
         );
     }
 
+    println!("\n\x1B[0;33mEXIT STATUS:\x1B[0m");
+
+    for (code, description) in errors::EXIT_CODES {
+        println!("    \x1B[0;32m{code}\x1B[0m  {description}");
+    }
+
     println!("\n\x1B[0;33mADDONS:\x1B[0m");
 
     for entry in std::fs::read_dir(&config.action_dir)? {
@@ -41,6 +41,43 @@ pub(crate) struct Opt {
     /// Disables TODOTXT_FINAL_FILTER
     #[arg(short = 'x')]
     pub disable_final_filter: bool,
+    /// Print a unified diff of the changes instead of writing any file
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Format of error messages
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub(crate) enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+impl Format {
+    /// `--format` read directly from the command line arguments, when they fail to parse.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        use clap::ValueEnum as _;
+
+        let mut format = Self::default();
+        let mut args = args.into_iter().take_while(|x| x != "--");
+
+        while let Some(arg) = args.next() {
+            let value = match arg.strip_prefix("--format") {
+                Some("") => args.next(),
+                Some(value) => value.strip_prefix('=').map(ToString::to_string),
+                None => continue,
+            };
+
+            if let Some(value) = value.and_then(|x| Self::from_str(&x, true).ok()) {
+                format = value;
+            }
+        }
+
+        format
+    }
 }
 
 #[derive(clap::Subcommand)]
@@ -50,6 +87,9 @@ pub(crate) enum Command {
     ///
     /// Project and context notation optional.
     /// Quotes optional.
+    ///
+    /// due: and t: accept today, tomorrow, yesterday, a weekday (mon, friday), +Nd, +Nw, +Nm,
+    /// +Ny (or -N), next-week, next-month, next-year, eow, eom and eoy besides YYYY-MM-DD.
     #[command(alias = "a")]
     Add(Add),
 
@@ -66,17 +106,47 @@ pub(crate) enum Command {
     /// addto DEST "TEXT TO ADD"
     Addto(AddTo),
 
+    /// Displays open tasks that contain TERM(s) grouped by due date: overdue, today, tomorrow, this
+    /// week, later and without date.
+    ///
+    /// Tasks due after the horizon (TODOTXT_AGENDA_HORIZON days, 30 by default) are not shown.
+    /// With --starts, deferred tasks are listed on their threshold date.
+    #[command(alias = "due")]
+    Agenda(Agenda),
+
     /// Adds TEXT TO APPEND to the end of the task on line ITEM#.
     ///
     /// Quotes optional.
+    ///
+    /// Dates in due: and t: are resolved like in add.
     #[command(alias = "app")]
     Append(Append),
 
     /// Moves all done tasks from todo.txt to done.txt and removes blank lines.
-    Archive,
+    ///
+    /// With TODOTXT_ARCHIVE_ROTATION=year or month, tasks go to done-YYYY.txt or done-YYYY-MM.txt
+    /// according to their completion date.
+    ///
+    /// A task and its subtasks (parent:ID) are archived together, once all of them are done.
+    Archive(Archive),
+
+    /// Draws the month of DATE, marking days with due tasks (!), threshold starts (>) and
+    /// completions (x), colored by the highest priority of the day.
+    ///
+    /// DATE is YYYY-MM, YYYY-MM-DD or a natural date like next-month; the current month by
+    /// default. With --week, lists the tasks of each day of the week instead.
+    Cal(Cal),
+
+    /// Removes duplicate lines from todo.txt, keeping the first occurrence in place.
+    ///
+    /// Like del, removed and merged lines are left blank unless -n is given.
+    Deduplicate(Deduplicate),
 
-    /// Removes duplicate lines from todo.txt.
-    Deduplicate,
+    /// Displays the tree of the tasks that task ITEM# depends on.
+    ///
+    /// A task depends on the tasks listed in its dep:ID tag, and on the tasks whose blocks:ID tag
+    /// contains its id. Several ids are separated by commas. Dependency cycles are reported.
+    Deps(Item),
 
     /// Deletes the task on line ITEM# in todo.txt.
     ///
@@ -89,6 +159,13 @@ pub(crate) enum Command {
     Delpri(Item),
 
     /// Marks task(s) on line ITEM# as done in todo.txt.
+    ///
+    /// A recurring task (rec:1w, rec:+1m, every:mon,fri, every:2nd-tue, every:last-bday) is
+    /// followed by its next occurrence not yet past due, unless until:DATE or count:N is reached.
+    ///
+    /// Tasks whose last open dependency was ITEM# are reported as unblocked. Marking a task with
+    /// open subtasks as done asks for confirmation. Subtasks and their parent are auto-archived
+    /// together, when the last of them is done.
     #[command(alias = "do")]
     Done(Item),
 
@@ -96,14 +173,34 @@ pub(crate) enum Command {
     #[cfg(feature = "extended")]
     Env,
 
-    /// List or flag task ITEM#.
+    /// Raises the priority of open tasks as their due date approaches.
+    ///
+    /// Rules are DAYS:PRIORITY pairs read from TODOTXT_ESCALATION (1:A,3:B by default): a task due
+    /// within DAYS gets at least PRIORITY. A priority is never lowered. With
+    /// TODOTXT_AUTO_ESCALATE=1, add, done and list also apply the rules.
+    Escalate,
+
+    /// Toggles the flag of task(s) on line ITEM# in todo.txt.
+    ///
+    /// Without ITEM#, lists flagged tasks.
     #[cfg(feature = "extended")]
     Flag(Flag),
 
+    /// Displays the streaks and completion rates of recurring tasks that contain TERM(s).
+    ///
+    /// Completions are read from done.txt, matching the habit:NAME tag or else the subject. Add
+    /// tasks with a creation date (-t) so that their completion date is recorded.
+    #[cfg(feature = "extended")]
+    Habits(Filter),
+
     /// Display help about usage, options, built-in and add-on actions, or just the usage help for
     /// the passed ACTION(s).
     Help,
 
+    /// Hides task(s) on line ITEM# from list, without deleting them.
+    #[cfg(feature = "extended")]
+    Hide(Items),
+
     /// Displays all tasks that contain TERM(s) sorted by priority with line numbers.
     ///
     /// Each task must match all TERM(s) (logical AND); to display tasks that contain any TERM
@@ -112,8 +209,12 @@ pub(crate) enum Command {
     /// grep-style basic regular expressions; for literal matching, put a single backslash before
     /// any [ ] \ $ * . ^ and enclose the entire TERM in single quotes, or use double backslashes
     /// and extra shell-quoting.  If no TERM specified, lists entire todo.txt.
+    ///
+    /// Tasks waiting for an open dependency (see deps) are hidden unless --blocked. With --tree,
+    /// subtasks, whose parent:ID tag designates their parent task, are indented under it and each
+    /// parent shows how many of its subtasks are done.
     #[command(alias = "ls")]
-    List(Filter),
+    List(List),
 
     /// Displays all the lines in todo.txt AND done.txt that contain TERM(s) sorted by priority
     /// with line numbers.
@@ -126,11 +227,13 @@ pub(crate) enum Command {
     /// Lists all added and overridden actions in the actions directory.
     Listaddons,
 
-    /// Lists all the task contexts that start with the @ sign in todo.txt.
+    /// Lists all the task contexts that start with the @ sign in todo.txt, with the number of
+    /// open, done and overdue tasks.
     ///
-    /// If TERM specified, considers only tasks that contain TERM(s).
+    /// If TERM specified, considers only tasks that contain TERM(s). With --tree, hierarchical
+    /// contexts (@home/garage, @home.office) are nested under their parent with rolled-up counts.
     #[command(alias = "lsc")]
-    Listcon(Filter),
+    Listcon(ListTag),
 
     /// Displays all the lines in SRC file located in the todo.txt directory, sorted by priority
     /// with line numbers.
@@ -141,19 +244,40 @@ pub(crate) enum Command {
     #[command(alias = "lf")]
     Listfile(ListFile),
 
+    /// Displays flagged tasks that contain TERM(s) sorted by priority with line numbers.
+    ///
+    /// Filters like list: hides all tasks that contain TERM(s) preceded by a minus sign (i.e.
+    /// -TERM).
+    #[cfg(feature = "extended")]
+    Listflag(Filter),
+
     /// Displays all tasks prioritized PRIORITIES.
     ///
-    /// PRIORITIES can be a single one (A) or a range (A-C). If no PRIORITIES specified, lists all
-    /// prioritized tasks. If TERM specified, lists only prioritized tasks that contain TERM(s).
-    /// Hides all tasks that contain TERM(s) preceded by a minus sign (i.e. -TERM).
+    /// PRIORITIES can be a single one (A), a range (A-C), an open-ended range (-B or C-), none for
+    /// tasks without priority, or a comma separated list of them (A,C-D,none). If no PRIORITIES
+    /// specified, lists all prioritized tasks. If TERM specified, lists only tasks matching
+    /// PRIORITIES that contain TERM(s). Hides all tasks that contain TERM(s) preceded by a minus
+    /// sign (i.e. -TERM).
     #[command(alias = "lsp")]
     Listpri(ListPri),
 
-    /// Lists all the projects (terms that start with a + sign) in todo.txt.
+    /// Lists all the projects (terms that start with a + sign) in todo.txt, with the number of
+    /// open, done and overdue tasks.
     ///
-    /// If TERM specified, considers only tasks that contain TERM(s).
+    /// If TERM specified, considers only tasks that contain TERM(s). With --tree, hierarchical
+    /// projects (+work.clientA.api, +work/clientB) are nested under their parent with rolled-up
+    /// counts.
     #[command(alias = "lsprj")]
-    Listproj(Filter),
+    Listproj(ListTag),
+
+    /// Lists the keys of all key:value tags used in todo.txt with the number of tasks carrying
+    /// them.
+    ///
+    /// If KEY specified, lists the distinct values of this tag instead. Tasks are filtered like
+    /// list: done, hidden and future tasks are ignored, and TERM(s) restrict the considered
+    /// tasks.
+    #[command(alias = "lst")]
+    Listtags(ListTags),
 
     /// Moves a line from source text file (SRC) to destination text file (DEST).
     ///
@@ -162,6 +286,13 @@ pub(crate) enum Command {
     #[command(alias = "mv")]
     Move(Move),
 
+    /// Displays the N most urgent actionable tasks that contain TERM, with their urgency score.
+    ///
+    /// Tasks waiting for an open dependency (see deps) are left out. The score combines priority,
+    /// due date, age, flag, projects and whether other tasks wait for it, weighted by
+    /// TODOTXT_URGENCY.
+    Next(Next),
+
     #[cfg(feature = "extended")]
     #[command(subcommand)]
     Note(Note),
@@ -169,6 +300,8 @@ pub(crate) enum Command {
     /// Adds TEXT TO PREPEND to the beginning of the task on line ITEM#.
     ///
     /// Quotes optional.
+    ///
+    /// Dates in due: and t: are resolved like in add.
     #[command(alias = "prep")]
     Prepend(Append),
 
@@ -185,17 +318,81 @@ pub(crate) enum Command {
     /// Increases the priority of the item number
     Priup(Item),
 
+    /// Moves the done task ITEM# back to todo.txt.
+    ///
+    /// ITEM# counts the lines of done.txt then of every rotated archive, in the `listall`
+    /// order. A #ID is searched in the same files. The recurring task created when
+    /// it was marked as done is removed.
+    Reopen(Item),
+
     /// Replaces task on line ITEM# with UPDATED TODO.
+    ///
+    /// Dates in due: and t: are resolved like in add.
     Replace(Replace),
 
     /// Adds the number of open tasks and done tasks to report.txt.
     Report,
 
+    /// Hides task ITEM# from list until WHEN by setting its threshold date.
+    ///
+    /// WHEN is a date like in add (monday, next-month, 2026-12-01). An offset (3d, +2w) bumps the
+    /// current threshold if it is still in the future.
+    #[command(alias = "defer")]
+    Snooze(Snooze),
+
+    /// Marks the done task on line ITEM# of todo.txt as not done.
+    ///
+    /// The recurring task created when it was marked as done is removed.
+    Undone(Item),
+
+    /// Removes the flag of task(s) on line ITEM# in todo.txt.
+    #[cfg(feature = "extended")]
+    Unflag(Items),
+
+    /// Shows again hidden task(s) on line ITEM# in list.
+    #[cfg(feature = "extended")]
+    Unhide(Items),
+
+    /// Removes the threshold date of task(s) on line ITEM#.
+    ///
+    /// Without ITEM#, removes the thresholds that have passed.
+    Wake(Wake),
+
     #[command(external_subcommand)]
     #[allow(dead_code)]
     External(Vec<String>),
 }
 
+impl Command {
+    /// Whether the command writes files and must hold the todo directory lock.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            #[cfg(feature = "extended")]
+            Self::Flag(arg) => !arg.items.is_empty(),
+            #[cfg(feature = "extended")]
+            Self::Habits(_) => false,
+            #[cfg(feature = "extended")]
+            Self::Env => false,
+            Self::Agenda(_)
+            | Self::Cal(_)
+            | Self::Deps(_)
+            | Self::Help
+            | Self::List(_)
+            | Self::Listall(_)
+            | Self::Listaddons
+            | Self::Listcon(_)
+            | Self::Listfile(_)
+            | Self::Listflag(_)
+            | Self::Listpri(_)
+            | Self::Listproj(_)
+            | Self::Listtags(_)
+            | Self::Next(_)
+            | Self::External(_) => false,
+            _ => true,
+        }
+    }
+}
+
 #[derive(clap::Parser)]
 pub(crate) struct Add {
     #[arg(default_value = "Vec::new")]
@@ -204,6 +401,7 @@ pub(crate) struct Add {
 
 #[derive(clap::Parser)]
 pub(crate) struct AddTo {
+    #[arg(add = clap_complete::ArgValueCompleter::new(txt_files))]
     pub dest: String,
     #[command(flatten)]
     pub add: Add,
@@ -211,52 +409,265 @@ pub(crate) struct AddTo {
 
 #[derive(clap::Parser)]
 pub(crate) struct Append {
-    pub item: usize,
+    pub item: TaskRef,
     #[command(flatten)]
     pub add: Add,
 }
 
 #[derive(clap::Parser)]
 pub(crate) struct Del {
-    pub item: usize,
+    pub item: TaskRef,
     #[command(flatten)]
     pub filter: Filter,
 }
 
 #[derive(clap::Parser)]
 pub(crate) struct Flag {
-    pub item: Option<usize>,
+    pub items: Vec<TaskRef>,
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct Agenda {
+    /// Number of days ahead to display
+    #[arg(long, value_name = "DAYS")]
+    pub horizon: Option<u64>,
+    /// Also list deferred tasks on their threshold date
+    #[arg(long)]
+    pub starts: bool,
+    #[command(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct Archive {
+    /// Only archive tasks completed more than AGE ago (30d, 2w, 6m, 1y)
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub older_than: Option<todo_txt::Date>,
+    /// Only archive tasks of PROJECT
+    #[arg(long, value_name = "+PROJECT")]
+    pub project: Option<String>,
+}
+
+/// `30d`, `2w`, `6m`… ago. A negated age is always an offset, the week start doesn't matter.
+fn parse_age(s: &str) -> Result<todo_txt::Date, String> {
+    let expr = format!("-{}", s.strip_prefix('+').unwrap_or(s));
+
+    crate::date::parse(&expr, todo_txt::date::today(), chrono::Weekday::Mon)
+        .ok_or_else(|| format!("invalid age '{s}'"))
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct Cal {
+    pub date: Option<String>,
+    /// Display the week of DATE with its tasks
+    #[arg(long)]
+    pub week: bool,
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct Deduplicate {
+    /// Ignore creation date, priority, whitespace and case when comparing tasks
+    #[arg(long)]
+    pub normalize: bool,
+    /// List tasks at least THRESHOLD similar (0.0 to 1.0) and ask to merge them
+    #[arg(long, value_name = "THRESHOLD")]
+    pub similar: Option<f32>,
 }
 
 #[derive(clap::Parser)]
 pub(crate) struct Item {
-    pub item: usize,
+    pub item: TaskRef,
 }
 
 #[derive(clap::Parser)]
+pub(crate) struct Items {
+    #[arg(required = true)]
+    pub items: Vec<TaskRef>,
+}
+
+/// A task designated by its line number (ITEM#) or by its id tag (#ID).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum TaskRef {
+    Line(usize),
+    Id(String),
+}
+
+impl std::str::FromStr for TaskRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(id) = s.strip_prefix('#') {
+            Ok(Self::Id(id.to_string()))
+        } else {
+            s.parse()
+                .map(Self::Line)
+                .map_err(|_| format!("invalid item '{s}', expected ITEM# or #ID"))
+        }
+    }
+}
+
+impl std::fmt::Display for TaskRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Line(line) => write!(f, "{line}"),
+            Self::Id(id) => write!(f, "#{id}"),
+        }
+    }
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct Next {
+    /// Number of tasks to display, 5 by default
+    #[arg(value_name = "N")]
+    count: Option<String>,
+    term: Option<String>,
+}
+
+impl Next {
+    /// `next [N] [TERM]`: a leading argument which isn't a number is the TERM.
+    pub fn split(&self) -> crate::Result<(usize, Filter)> {
+        let Some(count) = &self.count else {
+            return Ok((5, Filter::default()));
+        };
+
+        match (count.parse(), &self.term) {
+            (Ok(count), term) => Ok((count, Filter { term: term.clone() })),
+            (Err(_), None) => Ok((
+                5,
+                Filter {
+                    term: Some(count.clone()),
+                },
+            )),
+            (Err(_), Some(_)) => Err(crate::Error::Usage(format!("invalid number '{count}'"))),
+        }
+    }
+}
+
+#[derive(Default, clap::Parser)]
 pub(crate) struct Filter {
     pub term: Option<String>,
 }
 
+#[derive(clap::Parser)]
+pub(crate) struct List {
+    /// Also list tasks waiting for an open dependency
+    #[arg(long)]
+    pub blocked: bool,
+    /// Show the id:ID tags
+    #[arg(long)]
+    pub ids: bool,
+    /// Also list hidden tasks
+    #[arg(long)]
+    pub show_hidden: bool,
+    /// Only list tasks whose threshold date is in the future
+    #[arg(long)]
+    pub snoozed: bool,
+    /// Indent subtasks (parent:ID) under their parent task
+    #[arg(long)]
+    pub tree: bool,
+    #[command(flatten)]
+    pub filter: Filter,
+}
+
 #[derive(clap::Parser)]
 pub(crate) struct ListFile {
-    pub src: String,
+    #[arg(add = clap_complete::ArgValueCompleter::new(txt_files))]
+    pub src: Option<String>,
     #[command(flatten)]
     pub filter: Filter,
 }
 
 #[derive(clap::Parser)]
 pub(crate) struct ListPri {
-    pub priority: Option<char>,
+    #[arg(allow_hyphen_values = true)]
+    pub priority: Option<Priorities>,
+    #[command(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct ListTag {
+    /// Nest hierarchical names under their parent
+    #[arg(long)]
+    pub tree: bool,
+    #[command(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Priorities(Vec<PrioritySelector>);
+
+#[derive(Clone, Debug)]
+enum PrioritySelector {
+    None,
+    Range(u8, u8),
+}
+
+impl Priorities {
+    pub fn matches(&self, priority: &todo_txt::Priority) -> bool {
+        let priority = u8::from(priority.clone());
+
+        self.0.iter().any(|selector| match selector {
+            PrioritySelector::None => priority == u8::from(todo_txt::Priority::lowest()),
+            PrioritySelector::Range(from, to) => (*from..=*to).contains(&priority),
+        })
+    }
+}
+
+impl std::str::FromStr for Priorities {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let letter = |c: &str, default: char| -> Result<u8, String> {
+            let mut chars = c.chars();
+
+            let c = match (chars.next(), chars.next()) {
+                (None, _) => default,
+                (Some(c), None) => c,
+                _ => return Err(format!("invalid priority '{c}'")),
+            };
+
+            todo_txt::Priority::try_from(c)
+                .map(u8::from)
+                .map_err(|err| err.to_string())
+        };
+
+        let mut selectors = Vec::new();
+
+        for part in s.split(',').map(str::trim) {
+            let selector = if part.eq_ignore_ascii_case("none") {
+                PrioritySelector::None
+            } else if let Some((from, to)) = part.split_once('-') {
+                let from = letter(from, 'A')?;
+                let to = letter(to, 'Z')?;
+
+                PrioritySelector::Range(from.min(to), from.max(to))
+            } else {
+                let priority = letter(part, '\0')?;
+
+                PrioritySelector::Range(priority, priority)
+            };
+
+            selectors.push(selector);
+        }
+
+        Ok(Self(selectors))
+    }
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct ListTags {
+    pub key: Option<String>,
     #[command(flatten)]
     pub filter: Filter,
 }
 
 #[derive(clap::Parser)]
 pub(crate) struct Move {
-    pub item: usize,
+    pub item: TaskRef,
+    #[arg(add = clap_complete::ArgValueCompleter::new(txt_files))]
     pub dest: String,
-    #[arg(default_value = "todo.txt")]
+    #[arg(default_value = "todo.txt", add = clap_complete::ArgValueCompleter::new(txt_files))]
     pub src: String,
 }
 
@@ -274,12 +685,39 @@ pub(crate) enum Note {
 
 #[derive(clap::Parser)]
 pub(crate) struct Pri {
-    pub item: usize,
+    pub item: TaskRef,
     pub priority: char,
 }
 
+#[derive(clap::Parser)]
+pub(crate) struct Snooze {
+    pub item: TaskRef,
+    pub when: String,
+}
+
+#[derive(clap::Parser)]
+pub(crate) struct Wake {
+    pub items: Vec<TaskRef>,
+}
+
 #[derive(clap::Parser)]
 pub(crate) struct Replace {
-    pub item: usize,
+    pub item: TaskRef,
     pub text: Option<String>,
 }
+
+fn txt_files(current: &std::ffi::OsStr) -> Vec<clap_complete::CompletionCandidate> {
+    let Ok(config) = crate::Config::from_env() else {
+        return Vec::new();
+    };
+    let current = current.to_string_lossy();
+
+    crate::commands::txt_files(&config)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|x| x.file_name())
+        .map(|x| x.to_string_lossy().to_string())
+        .filter(|x| x.starts_with(current.as_ref()))
+        .map(clap_complete::CompletionCandidate::new)
+        .collect()
+}
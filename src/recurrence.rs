@@ -0,0 +1,106 @@
+//! Recurrence rules of the `every:` tag, completing the `rec:` intervals of todo-txt.
+//!
+//! - `every:mon,wed,fri`: the listed weekdays;
+//! - `every:2nd-tue`, `every:last-fri`: the nth (1st to 5th) or last weekday of the month;
+//! - `every:last-bday`: the last business day (monday to friday) of the month.
+//!
+//! Both `rec:` and `every:` stop after the `until:YYYY-MM-DD` date or after `count:N`
+//! occurrences.
+
+use chrono::Datelike as _;
+
+pub const EVERY_TAG: &str = "every";
+pub const UNTIL_TAG: &str = "until";
+pub const COUNT_TAG: &str = "count";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Every {
+    Weekdays(Vec<chrono::Weekday>),
+    NthWeekday(u32, chrono::Weekday),
+    LastWeekday(chrono::Weekday),
+    LastBusinessDay,
+}
+
+impl Every {
+    /// First occurrence strictly after `date`.
+    pub fn next(&self, date: todo_txt::Date) -> Option<todo_txt::Date> {
+        if let Self::Weekdays(weekdays) = self {
+            return date
+                .iter_days()
+                .skip(1)
+                .take(7)
+                .find(|x| weekdays.contains(&x.weekday()));
+        }
+
+        let month = crate::date::start_of_month(date);
+
+        // A 5th weekday may be missing for a few months, never for a whole year.
+        (0..24)
+            .filter_map(|x| month.checked_add_months(chrono::Months::new(x)))
+            .filter_map(|x| self.in_month(x))
+            .find(|x| *x > date)
+    }
+
+    fn in_month(&self, month: todo_txt::Date) -> Option<todo_txt::Date> {
+        let last = crate::date::end_of_month(month);
+
+        match self {
+            Self::Weekdays(_) => None,
+            Self::NthWeekday(nth, weekday) => {
+                let first = weekday.days_since(month.weekday()) + 1;
+
+                month
+                    .with_day(first + 7 * (nth - 1))
+                    .filter(|x| x.month() == month.month())
+            }
+            Self::LastWeekday(weekday) => {
+                Some(last - chrono::Days::new(last.weekday().days_since(*weekday) as u64))
+            }
+            Self::LastBusinessDay => {
+                let mut day = last;
+
+                while matches!(day.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun) {
+                    day = day.pred_opt()?;
+                }
+
+                Some(day)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Every {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || crate::Error::Parse(format!("Invalid recurrence: {s}"));
+        let s = s.to_lowercase();
+
+        if s == "last-bday" || s == "last-business-day" {
+            return Ok(Self::LastBusinessDay);
+        }
+
+        if let Some((nth, weekday)) = s.split_once('-') {
+            let weekday = weekday.parse().map_err(|_| invalid())?;
+
+            let nth = match nth {
+                "1st" => 1,
+                "2nd" => 2,
+                "3rd" => 3,
+                "4th" => 4,
+                "5th" => 5,
+                "last" => return Ok(Self::LastWeekday(weekday)),
+                _ => return Err(invalid()),
+            };
+
+            return Ok(Self::NthWeekday(nth, weekday));
+        }
+
+        let weekdays = s
+            .split(',')
+            .map(|x| x.parse().map_err(|_| invalid()))
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        Ok(Self::Weekdays(weekdays))
+    }
+}
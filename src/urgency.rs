@@ -0,0 +1,104 @@
+//! Urgency of a task, the weighted sum of factors ranging from 0 to 1:
+//!
+//! - `priority`: 1 for (A), decreasing down to 0 for a task without priority;
+//! - `due`: 0.2 for a due date two weeks or more ahead, growing up to 1 a week past due;
+//! - `age`: days elapsed since the creation date over a year, at most 1;
+//! - `flag`: 1 for a flagged task;
+//! - `blocking`: 1 while an open task depends on it (`dep:ID` or `blocks:ID`);
+//! - `+PROJECT`: 1 for a task of this project.
+//!
+//! `TODOTXT_URGENCY` overrides the default weights with `KEY:WEIGHT` pairs like
+//! `due:8,blocking:10,+work:3`.
+
+/// Weights of the urgency factors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    priority: f64,
+    due: f64,
+    age: f64,
+    flag: f64,
+    blocking: f64,
+    projects: std::collections::BTreeMap<String, f64>,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            priority: 6.,
+            due: 12.,
+            age: 2.,
+            flag: 4.,
+            blocking: 8.,
+            projects: Default::default(),
+        }
+    }
+}
+
+impl Weights {
+    pub fn score(&self, task: &crate::Task, blocking: bool, today: todo_txt::Date) -> f64 {
+        let priority = if task.priority.is_lowest() {
+            0.
+        } else {
+            f64::from(26 - u8::from(task.priority.clone())) / 26.
+        };
+
+        let due = task.due_date.map_or(0., |due_date| {
+            let days = (due_date - today).num_days().clamp(-7, 14) as f64;
+
+            0.2 + (14. - days) * 0.8 / 21.
+        });
+
+        let age = task.create_date.map_or(0., |create_date| {
+            ((today - create_date).num_days().max(0) as f64 / 365.).min(1.)
+        });
+
+        #[cfg(feature = "extended")]
+        let flag = if task.flagged { 1. } else { 0. };
+        #[cfg(not(feature = "extended"))]
+        let flag = 0.;
+
+        let blocking = if blocking { 1. } else { 0. };
+
+        let projects = task
+            .projects
+            .iter()
+            .filter_map(|x| self.projects.get(x))
+            .sum::<f64>();
+
+        self.priority * priority
+            + self.due * due
+            + self.age * age
+            + self.flag * flag
+            + self.blocking * blocking
+            + projects
+    }
+}
+
+impl std::str::FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+
+        for pair in s.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let invalid = || format!("Invalid urgency weight: {pair}");
+
+            let (key, weight) = pair.split_once(':').ok_or_else(invalid)?;
+            let weight = weight.trim().parse().map_err(|_| invalid())?;
+
+            match key.trim() {
+                "priority" => weights.priority = weight,
+                "due" => weights.due = weight,
+                "age" => weights.age = weight,
+                "flag" => weights.flag = weight,
+                "blocking" => weights.blocking = weight,
+                key => {
+                    let project = key.strip_prefix('+').ok_or_else(invalid)?;
+                    weights.projects.insert(project.to_string(), weight);
+                }
+            }
+        }
+
+        Ok(weights)
+    }
+}
@@ -9,6 +9,12 @@ struct Result {
     report: String,
 }
 
+#[derive(Debug)]
+struct Failure {
+    code: Option<i32>,
+    stderr: String,
+}
+
 #[test]
 fn action() {
     use std::os::unix::prelude::PermissionsExt;
@@ -30,6 +36,32 @@ fn add() {
     assert_eq!(result.stdout, format!("1 new task\n"));
 }
 
+#[test]
+fn add_natural_date() {
+    use chrono::Datelike;
+
+    let today = chrono::Local::now().date_naive();
+    let tomorrow = today.succ_opt().unwrap();
+    let in_2_weeks = today + chrono::Days::new(14);
+    let eoy = chrono::NaiveDate::from_ymd_opt(today.year(), 12, 31).unwrap();
+
+    let result = exec("add", &["new task due:tomorrow t:-0d"]);
+    assert_eq!(result.todo, format!("new task due:{tomorrow} t:{today}\n"));
+
+    let result = reexec(
+        result.todo_dir,
+        "replace",
+        &["1", "new task t:eoy id:tomorrow"],
+    );
+    assert_eq!(result.todo, format!("new task t:{eoy} id:tomorrow\n"));
+
+    let result = reexec(result.todo_dir, "append", &["1", "due:+2w"]);
+    assert_eq!(
+        result.todo,
+        format!("new task due:{in_2_weeks} t:{eoy} id:tomorrow\n")
+    );
+}
+
 #[test]
 fn addm() {
     let task = "new task 1\nnew task 2";
@@ -46,6 +78,39 @@ fn addto() {
     assert_eq!(result.done, format!("{task}\n"));
 }
 
+#[test]
+fn agenda() {
+    let today = chrono::Local::now().date_naive();
+    let yesterday = today.pred_opt().unwrap();
+    let tomorrow = today.succ_opt().unwrap();
+    let start = today + chrono::Days::new(40);
+
+    let task = "late due:yesterday\nnow due:today\nsoon due:tomorrow\nsomeday\nnext year due:+1y\ndeferred t:+40d";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let envs = HashMap::from([("TODOTXT_RELDATE", "false")]);
+    let result = reexec_env(todo_dir, "agenda", &[], envs.clone());
+    assert_eq!(
+        result.stdout,
+        format!(
+            "Overdue:\n1 late due:{yesterday}\nToday:\n2 now due:{today}\nTomorrow:\n3 soon due:{tomorrow}\nNo date:\n4 someday\n--\nTODO: 4 of 6 tasks show\n"
+        )
+    );
+
+    let result = reexec_env(
+        result.todo_dir,
+        "due",
+        &["--starts", "--horizon", "60", "e"],
+        envs,
+    );
+    assert_eq!(
+        result.stdout,
+        format!(
+            "Overdue:\n1 late due:{yesterday}\nLater:\n6 deferred t:{start} (starts)\nNo date:\n4 someday\n--\nTODO: 3 of 6 tasks show\n"
+        )
+    );
+}
+
 #[test]
 fn append() {
     let task = "new task";
@@ -70,14 +135,158 @@ fn archive() {
     );
 }
 
+#[test]
+fn archive_policies() {
+    let task = "x 2021-03-01 2021-01-01 old task +work\nx 2021-03-02 2021-01-01 other task +home\nnew task";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let envs = HashMap::from([("TODOTXT_ARCHIVE_ROTATION", "month")]);
+    let result = reexec_env(todo_dir, "archive", &["--project", "+home"], envs);
+    assert_eq!(
+        result.todo,
+        "x 2021-03-01 2021-01-01 old task +work\nnew task\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(result.todo_dir.join("done-2021-03.txt")).unwrap(),
+        "x 2021-03-02 2021-01-01 other task +home\n"
+    );
+
+    let failure = failure(&result.todo_dir, "archive", &["--older-than", "30dz"]);
+    assert_eq!(failure.code, Some(2));
+    assert!(failure.stderr.contains("invalid age '30dz'"));
+
+    let envs = HashMap::from([("TODOTXT_ARCHIVE_ROTATION", "year")]);
+    let result = reexec_env(result.todo_dir, "archive", &["--older-than", "1y"], envs);
+    assert_eq!(result.todo, "new task\n");
+    assert_eq!(
+        std::fs::read_to_string(result.todo_dir.join("done-2021.txt")).unwrap(),
+        "x 2021-03-01 2021-01-01 old task +work\n"
+    );
+
+    let result = reexec(result.todo_dir, "listall", &[]);
+    assert_eq!(
+        result.stdout,
+        r#"1 new task
+0 x 2021-03-01 2021-01-01 old task +work
+0 x 2021-03-02 2021-01-01 other task +home
+--
+TODO: 1 of 1 tasks show
+DONE: 0 of 0 tasks show
+DONE-2021: 1 of 1 tasks show
+DONE-2021-03: 1 of 1 tasks show
+total: 3 of 3 tasks show
+"#
+    );
+}
+
+#[test]
+fn dry_run() {
+    let task = "new task\nx done task";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "--dry-run", &["archive"]);
+    assert_eq!(result.todo, "new task\nx done task\n");
+    assert_eq!(result.done, "");
+
+    let todo_file = format!("{}/todo.txt", result.todo_dir.display());
+    let done_file = format!("{}/done.txt", result.todo_dir.display());
+    assert_eq!(
+        result.stdout,
+        format!(
+            "--- {todo_file}\n+++ {todo_file}\n@@ -1,2 +1 @@\n new task\n-x done task\n--- {done_file}\n+++ {done_file}\n@@ -0,0 +1 @@\n+x done task\nTODO: {todo_file} archived\n"
+        )
+    );
+    assert!(!result.todo_dir.join("done.txt").exists());
+
+    let failure = failure(&result.todo_dir, "--dry-run", &["addon"]);
+    assert_eq!(failure.code, Some(2));
+    assert_eq!(failure.stderr, "TODO: addon does not support --dry-run\n");
+}
+
+#[test]
+fn cal() {
+    let task = "(A) pay rent due:2021-02-05\nfile taxes t:2021-02-10";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+    let Result { todo_dir, .. } = reexec(
+        todo_dir,
+        "addto",
+        &["done.txt", "x 2021-02-12 2021-02-01 call bob"],
+    );
+
+    let result = reexec(todo_dir, "cal", &["2021-02"]);
+    assert_eq!(
+        result.stdout,
+        r#"       February 2021
+Mo  Tu  We  Th  Fr  Sa  Su
+ 1   2   3   4   5!  6   7
+ 8   9  10> 11  12x 13  14
+15  16  17  18  19  20  21
+22  23  24  25  26  27  28
+! due  > starts  x done
+"#
+    );
+
+    let result = reexec(result.todo_dir, "cal", &["--week", "2021-02-10"]);
+    assert_eq!(
+        result.stdout,
+        r#"Mon 2021-02-08
+Tue 2021-02-09
+Wed 2021-02-10
+  > 2 file taxes t:2021-02-10
+Thu 2021-02-11
+Fri 2021-02-12
+  x 0 x 2021-02-12 2021-02-01 call bob
+Sat 2021-02-13
+Sun 2021-02-14
+"#
+    );
+}
+
 #[test]
 fn deduplicate() {
-    let task = "new task 1\nnew task 2\nnew task 1";
+    let task = "new task 2\nnew task 1\nnew task 2";
     let Result { todo_dir, .. } = exec("addm", &[task]);
 
     let result = reexec(todo_dir, "deduplicate", &[]);
-    assert_eq!(result.todo, "new task 1\nnew task 2\n");
+    assert_eq!(result.todo, "new task 2\nnew task 1\n\n");
     assert_eq!(result.stdout, "TODO: 1 duplicate task(s) removed\n");
+
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+    let result = reexec(todo_dir, "-n", &["deduplicate"]);
+    assert_eq!(result.todo, "new task 2\nnew task 1\n");
+
+    let task = "(A) 2021-01-01 Buy Milk\nbuy  milk\nreview PR +cli\nreview the PR @work";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(
+        todo_dir,
+        "deduplicate",
+        &["--normalize", "--similar", "0.6"],
+    );
+    assert_eq!(
+        result.todo,
+        "(A) 2021-01-01 Buy Milk\n\nreview PR +cli @work\n\n"
+    );
+    assert_eq!(
+        result.stdout,
+        "TODO: 1 duplicate task(s) removed\n3 review PR +cli\n4 review the PR @work\nTODO: 1 similar task(s) merged\n"
+    );
+
+    let Result { todo_dir, .. } = exec("add", &["a"]);
+    std::fs::write(todo_dir.join("todo.txt"), "a\n\nb\nc\n").unwrap();
+
+    let result = reexec(todo_dir, "deduplicate", &["--similar", "0.9"]);
+    assert_eq!(result.todo, "a\n\nb\nc\n");
+    assert_eq!(result.stdout, "TODO: No duplicate tasks found\n");
+
+    std::fs::write(
+        result.todo_dir.join("todo.txt"),
+        "a\n\nb\na\nc\nreview PR\nreview PR!\n",
+    )
+    .unwrap();
+
+    let result = reexec(result.todo_dir, "deduplicate", &["--similar", "0.9"]);
+    assert_eq!(result.todo, "a\n\nb\n\nc\nreview PR\n\n");
 }
 
 #[test]
@@ -86,12 +295,37 @@ fn del() {
     let Result { todo_dir, .. } = exec("addm", &[task]);
 
     let result = reexec(todo_dir, "del", &["1"]);
-    assert_eq!(result.todo, "new task 2\n");
+    assert_eq!(result.todo, "\nnew task 2\n");
     assert_eq!(result.stdout, "1 new task 1\nTODO: 1 deleted.\n");
+
+    let result = reexec(result.todo_dir, "list", &[]);
+    assert_eq!(result.stdout, "2 new task 2\n--\nTODO: 1 of 1 tasks show\n");
+
+    let result = reexec(result.todo_dir, "-n", &["del", "2"]);
+    assert_eq!(result.todo, "\n");
+
+    let result = reexec(result.todo_dir, "archive", &[]);
+    assert_eq!(result.todo, "");
 }
 
 #[test]
-fn del_term() {}
+fn del_term() {
+    let task = "new task 1 @home";
+    let Result { todo_dir, .. } = exec("add", &[task]);
+
+    let result = reexec(todo_dir, "del", &["1", "@home"]);
+    assert_eq!(result.todo, "new task 1\n");
+    assert_eq!(
+        result.stdout,
+        "1 new task 1 @home\nTODO: Removed '@home' from task.\n1 new task 1\n"
+    );
+
+    let failure = failure(&result.todo_dir, "del", &["1", "@work"]);
+    assert_eq!(
+        failure.stderr,
+        "TODO: '@work' not found; no removal done.\n"
+    );
+}
 
 #[test]
 fn delpri() {
@@ -108,12 +342,198 @@ fn done() {
     let task = "new task 1\nnew task 2";
     let Result { todo_dir, .. } = exec("addm", &[task]);
 
-    let result = reexec(todo_dir, "done", &["2"]);
-    assert_eq!(result.todo, "new task 1\n");
-    assert_eq!(result.done, "x new task 2\n");
+    let result = reexec(todo_dir, "done", &["1"]);
+    assert_eq!(result.todo, "\nnew task 2\n");
+    assert_eq!(result.done, "x new task 1\n");
+
+    let result = reexec(result.todo_dir, "-n", &["done", "2"]);
+    assert_eq!(result.todo, "\n");
+    assert_eq!(result.done, "x new task 1\nx new task 2\n");
     assert_eq!(result.stdout, "2 x new task 2\nTODO: 2 marked as done.\n");
 }
 
+#[test]
+fn no_task() {
+    let task = "new task 1";
+    let Result { todo_dir, .. } = exec("add", &[task]);
+
+    let commands: &[(&str, &[&str])] = &[
+        ("append", &["text"]),
+        ("del", &[]),
+        ("del", &["task"]),
+        ("done", &[]),
+        ("pri", &["A"]),
+        ("replace", &["text"]),
+    ];
+
+    for (command, args) in commands {
+        for item in ["0", "2"] {
+            let failure = failure(&todo_dir, command, &[&[item], *args].concat());
+            assert_eq!(failure.code, Some(1));
+            assert_eq!(failure.stderr, format!("TODO: No task {item}.\n"));
+        }
+    }
+
+    let failure = failure(&todo_dir, "move", &["2", "done.txt"]);
+    assert_eq!(failure.stderr, "TODO: No task 2.\n");
+}
+
+#[test]
+fn exit_code() {
+    use std::os::unix::prelude::PermissionsExt;
+
+    let task = "new task 1";
+    let Result { todo_dir, .. } = exec("add", &[task]);
+
+    let failure = self::failure(&todo_dir, "pri", &["1"]);
+    assert_eq!(failure.code, Some(2));
+
+    let failure = self::failure(&todo_dir, "pri", &["1", "1"]);
+    assert_eq!(failure.code, Some(2));
+    assert_eq!(failure.stderr, "TODO: Invalid priority: 1\n");
+
+    let command = todo_dir.join("broken");
+    std::fs::write(&command, "#!/bin/bash\nexit 3").unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let failure = self::failure(&todo_dir, "broken", &[]);
+    assert_eq!(failure.code, Some(5));
+
+    let assert = self::command(&todo_dir, "del", &["1"], HashMap::new())
+        .env("TODOTXT_FORCE", "false")
+        .write_stdin("n\n")
+        .assert()
+        .failure();
+    assert_eq!(assert.get_output().status.code(), Some(6));
+
+    let lock = std::fs::File::create(todo_dir.join(".todo.lock")).unwrap();
+    lock.lock().unwrap();
+    let failure = self::failure(&todo_dir, "add", &["task"]);
+    assert_eq!(failure.code, Some(4));
+
+    let result = reexec(todo_dir, "list", &[]);
+    assert_eq!(result.todo, "new task 1\n");
+
+    // A lock file left by an interrupted command is not held anymore.
+    drop(lock);
+    let result = reexec(result.todo_dir, "add", &["new task 2"]);
+    assert_eq!(result.todo, "new task 1\nnew task 2\n");
+}
+
+#[test]
+fn completion() {
+    let Result { todo_dir, .. } = exec("add", &["new task 1"]);
+    std::fs::write(todo_dir.join("work.txt"), "").unwrap();
+
+    let envs = HashMap::from([("COMPLETE", "bash"), ("_CLAP_COMPLETE_INDEX", "2")]);
+    let assert = self::command(
+        &todo_dir,
+        "--",
+        &[env!("CARGO_PKG_NAME"), "listfile", "w"],
+        envs,
+    )
+    .assert()
+    .success();
+    assert_eq!(
+        String::from_utf8_lossy(&assert.get_output().stdout),
+        "work.txt"
+    );
+}
+
+#[test]
+fn json_error() {
+    let task = "new task 1";
+    let Result { todo_dir, .. } = exec("add", &[task]);
+
+    let failure = failure(&todo_dir, "--format", &["json", "done", "9"]);
+    assert_eq!(failure.code, Some(1));
+    assert_eq!(
+        failure.stderr,
+        "{\"error\":\"no_task\",\"code\":1,\"message\":\"TODO: No task 9.\"}\n"
+    );
+
+    let assert = self::command(
+        &todo_dir,
+        "--format",
+        &["json", "do", "abc"],
+        HashMap::new(),
+    )
+    .assert()
+    .failure();
+    let output = assert.get_output();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).starts_with("{\"error\":\"usage\",\"code\":2,")
+    );
+
+    let envs = HashMap::from([("TODOTXT_ESCALATION", "foo")]);
+    let assert = self::command(&todo_dir, "--format", &["json", "list"], envs)
+        .assert()
+        .failure();
+    let output = assert.get_output();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).starts_with("{\"error\":\"config\",\"code\":2,")
+    );
+}
+
+#[test]
+fn id() {
+    let mut envs = HashMap::new();
+    envs.insert("NO_COLOR", "true");
+    envs.insert("TODOTXT_ID_ON_ADD", "true");
+
+    let result = reexec_env(create_dir(), "addm", &["new task 1\nnew task 2"], envs);
+    let ids = result
+        .todo
+        .lines()
+        .map(|x| x.rsplit_once("id:").unwrap().1.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+
+    let result = reexec(result.todo_dir, "del", &[&format!("#{}", ids[0])]);
+    assert_eq!(
+        result.stdout,
+        format!("1 new task 1 id:{}\nTODO: 1 deleted.\n", ids[0])
+    );
+
+    let result = reexec(
+        result.todo_dir,
+        "replace",
+        &[&format!("#{}", ids[1]), "task 2"],
+    );
+    assert_eq!(result.todo, format!("\ntask 2 id:{}\n", ids[1]));
+
+    let result = reexec(result.todo_dir, "list", &[]);
+    assert_eq!(result.stdout, "2 task 2\n--\nTODO: 1 of 1 tasks show\n");
+
+    let result = reexec(result.todo_dir, "list", &["--ids"]);
+    assert_eq!(
+        result.stdout,
+        format!("2 task 2 id:{}\n--\nTODO: 1 of 1 tasks show\n", ids[1])
+    );
+
+    let failure = failure(&result.todo_dir, "done", &[&format!("#{}", ids[0])]);
+    assert_eq!(failure.stderr, format!("TODO: No task #{}.\n", ids[0]));
+}
+
+#[test]
+fn blank_line() {
+    let todo_dir = create_dir();
+    std::fs::write(todo_dir.join("todo.txt"), "new task 1\n\nnew task 3\n").unwrap();
+
+    let failure = failure(&todo_dir, "done", &["2"]);
+    assert_eq!(failure.stderr, "TODO: No task 2.\n");
+
+    let result = reexec(todo_dir, "list", &[]);
+    assert_eq!(
+        result.stdout,
+        "1 new task 1\n3 new task 3\n--\nTODO: 2 of 2 tasks show\n"
+    );
+}
+
 #[test]
 fn list() {
     let task = "new task 1\nnew task 2";
@@ -149,12 +569,91 @@ fn flag() {
     assert_eq!(result.stdout, "--\nTODO: 0 of 1 tasks show\n");
 
     let result = reexec(result.todo_dir, "flag", &["1"]);
+    assert_eq!(result.stdout, "1 new task 1 f:1\nTODO: 1 flagged.\n");
 
     let result = reexec(result.todo_dir, "flag", &[]);
     assert_eq!(
         result.stdout,
         "1  new task 1\n--\nTODO: 1 of 1 tasks show\n"
     );
+
+    let Result { todo_dir, .. } = reexec(result.todo_dir, "add", &["new task 2"]);
+    let result = reexec(todo_dir, "flag", &["1", "2"]);
+    assert_eq!(
+        result.stdout,
+        "1 new task 1\nTODO: 1 unflagged.\n2 new task 2 f:1\nTODO: 2 flagged.\n"
+    );
+
+    let Result { todo_dir, .. } = reexec(result.todo_dir, "flag", &["1"]);
+    let result = reexec(todo_dir, "listflag", &["2"]);
+    assert_eq!(
+        result.stdout,
+        "2  new task 2\n--\nTODO: 1 of 2 tasks show\n"
+    );
+
+    let result = reexec(result.todo_dir, "unflag", &["1", "2"]);
+    assert_eq!(result.todo, "new task 1\nnew task 2\n");
+}
+
+#[test]
+#[cfg(feature = "extended")]
+fn habits() {
+    let today = chrono::Local::now().date_naive();
+    let ago = |n| today - chrono::Days::new(n);
+
+    let Result { todo_dir, .. } = exec("add", &["2021-01-01 water plants rec:1d"]);
+
+    for n in [0, 1, 2, 10] {
+        let task = format!("x {} 2021-01-01 water plants rec:1d", ago(n));
+        reexec(todo_dir.clone(), "addto", &["done.txt", &task]);
+    }
+
+    let sparkline = (0..30)
+        .rev()
+        .map(|n| {
+            if [0, 1, 2, 10].contains(&n) {
+                '█'
+            } else {
+                '▁'
+            }
+        })
+        .collect::<String>();
+
+    let result = reexec(todo_dir, "habits", &[]);
+    assert_eq!(
+        result.stdout,
+        format!("water plants\n  {sparkline}  streak 3 (best 3)  30d 13%  90d 4%\n")
+    );
+
+    let result = reexec(result.todo_dir, "done", &["1"]);
+    assert!(
+        result
+            .done
+            .ends_with(&format!("x {today} 2021-01-01 water plants rec:1d\n"))
+    );
+}
+
+#[test]
+#[cfg(feature = "extended")]
+fn hide() {
+    let task = "new task 1\nnew task 2";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "hide", &["1"]);
+    assert_eq!(result.todo, "new task 1 h:1\nnew task 2\n");
+    assert_eq!(result.stdout, "1 new task 1 h:1\nTODO: 1 hidden.\n");
+
+    let result = reexec(result.todo_dir, "list", &[]);
+    assert_eq!(result.stdout, "2 new task 2\n--\nTODO: 1 of 2 tasks show\n");
+
+    let result = reexec(result.todo_dir, "list", &["--show-hidden"]);
+    assert_eq!(
+        result.stdout,
+        "1 new task 1 h:1\n2 new task 2\n--\nTODO: 2 of 2 tasks show\n"
+    );
+
+    let result = reexec(result.todo_dir, "unhide", &["1"]);
+    assert_eq!(result.todo, "new task 1\nnew task 2\n");
 }
 
 #[test]
@@ -293,7 +792,7 @@ fn listcon() {
     let todo_dir = setup();
 
     let result = reexec(todo_dir, "listcon", &[]);
-    assert_eq!(result.stdout, "CIC\n");
+    assert_eq!(result.stdout, "CIC (2 open, 0 done, 1 overdue)\n");
 }
 
 #[test]
@@ -322,6 +821,19 @@ DONE: 1 of 1 tasks show
     );
 }
 
+#[test]
+fn listfile_without_src() {
+    let task = "new task 1\nnew task 2\nx done task";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+    let Result { todo_dir, .. } = reexec(todo_dir, "addto", &["inbox.txt", "inbox task"]);
+
+    let result = reexec(todo_dir, "listfile", &[]);
+    let lines = result.stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("inbox.txt: 1 tasks (1 open, 0 done), modified "));
+    assert!(lines[1].starts_with("todo.txt: 3 tasks (2 open, 1 done), modified "));
+}
+
 #[test]
 fn listpri() {
     let todo_dir = setup();
@@ -338,15 +850,140 @@ TODO: 3 of 9 tasks show
     );
 }
 
+#[test]
+fn listpri_range() {
+    let todo_dir = setup();
+
+    let result = reexec(todo_dir, "listpri", &["a-b"]);
+    assert_eq!(
+        result.stdout,
+        r#"1 (A) Make peace between Cylons and humans +PeaceProject
+2 (B) Report to <i>Admiral Adama</i> about FTL @CIC +Galactica\Repairs due:2013-05-24
+--
+TODO: 2 of 9 tasks show
+"#
+    );
+
+    let result = reexec(result.todo_dir, "listpri", &["A,C", "cylon"]);
+    assert_eq!(
+        result.stdout,
+        r#"1 (A) Make peace between Cylons and humans +PeaceProject
+4 (C) Upgrade jump drives with Cylon technology +Galactica\Repairs
+--
+TODO: 2 of 9 tasks show
+"#
+    );
+
+    let result = reexec(result.todo_dir, "listpri", &["-B", "repairs"]);
+    assert_eq!(
+        result.stdout,
+        r#"2 (B) Report to <i>Admiral Adama</i> about FTL @CIC +Galactica\Repairs due:2013-05-24
+--
+TODO: 1 of 9 tasks show
+"#
+    );
+
+    let result = reexec(result.todo_dir, "listpri", &["C-"]);
+    assert_eq!(
+        result.stdout,
+        r#"4 (C) Upgrade jump drives with Cylon technology +Galactica\Repairs
+--
+TODO: 1 of 9 tasks show
+"#
+    );
+
+    let result = reexec(result.todo_dir, "listpri", &["none", "cic"]);
+    assert_eq!(
+        result.stdout,
+        r#"6 Check for <b>DRADIS</b> contact @CIC
+--
+TODO: 1 of 9 tasks show
+"#
+    );
+}
+
 #[test]
 fn listproj() {
     let todo_dir = setup();
 
     let result = reexec(todo_dir, "listproj", &[]);
-    assert_eq!(result.stdout, "PeaceProject\nGalactica\\Repairs\n");
+    assert_eq!(
+        result.stdout,
+        "Galactica\\Repairs (3 open, 0 done, 2 overdue)\nPeaceProject (1 open, 0 done, 0 overdue)\n"
+    );
 
     let result = reexec(result.todo_dir, "listproj", &["galactica"]);
-    assert_eq!(result.stdout, "Galactica\\Repairs\n");
+    assert_eq!(
+        result.stdout,
+        "Galactica\\Repairs (3 open, 0 done, 2 overdue)\n"
+    );
+}
+
+#[test]
+fn listproj_tree() {
+    let task = "api +work.clientA.api\nweb +work.clientA.web\nx ops +work.clientB\nmixed +work.clientA.api +work.clientA.web\nlawn +home";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "listproj", &["--tree"]);
+    assert_eq!(
+        result.stdout,
+        r#"home (1 open, 0 done, 0 overdue)
+work (3 open, 1 done, 0 overdue)
+  clientA (3 open, 0 done, 0 overdue)
+    api (2 open, 0 done, 0 overdue)
+    web (2 open, 0 done, 0 overdue)
+  clientB (0 open, 1 done, 0 overdue)
+"#
+    );
+}
+
+#[test]
+fn listproj_sigil() {
+    let task = "first (+paren) task\nsecond +Project task\nemail me+x@example.com";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "listproj", &[]);
+    assert_eq!(result.stdout, "Project (1 open, 0 done, 0 overdue)\n");
+
+    let mut envs = HashMap::new();
+    envs.insert("TODOTXT_SIGIL_BEFORE_PATTERN", "\\(");
+    envs.insert("TODOTXT_SIGIL_AFTER_PATTERN", "\\)");
+    envs.insert("TODOTXT_SIGIL_VALID_PATTERN", "[A-Za-z]+");
+
+    let result = reexec_env(result.todo_dir, "listproj", &[], envs);
+    assert_eq!(
+        result.stdout,
+        "Project (1 open, 0 done, 0 overdue)\nparen (1 open, 0 done, 0 overdue)\n"
+    );
+}
+
+#[test]
+fn listproj_sourcevar() {
+    let todo_dir = setup();
+
+    let mut envs = HashMap::new();
+    envs.insert("TODOTXT_SOURCEVAR", "$DONE_FILE");
+
+    let result = reexec_env(todo_dir, "listproj", &[], envs);
+    assert_eq!(
+        result.stdout,
+        "Galactica\\Repairs (0 open, 1 done, 0 overdue)\n"
+    );
+}
+
+#[test]
+fn listtags() {
+    let task = "task 1 client:acme sprint:12\ntask 2 client:acme\ntask 3 client:globex\nx done client:initech";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "listtags", &[]);
+    assert_eq!(result.stdout, "client (3 tasks)\nsprint (1 tasks)\n");
+
+    let result = reexec(result.todo_dir, "listtags", &["client"]);
+    assert_eq!(result.stdout, "acme (2 tasks)\nglobex (1 tasks)\n");
+
+    let result = reexec(result.todo_dir, "listtags", &["client", "3"]);
+    assert_eq!(result.stdout, "globex (1 tasks)\n");
 }
 
 #[test]
@@ -406,20 +1043,312 @@ fn pri() {
 #[test]
 #[cfg(feature = "extended")]
 fn recurrence() {
-    let task = "new task 1 t:2020-01-01 due:2020-02-02 rec:+1y";
+    let task = "new task 1 t:2098-01-01 due:2098-02-02 rec:+1y";
     let Result { todo_dir, .. } = exec("add", &[task]);
 
     let result = reexec(todo_dir, "done", &["1"]);
     assert_eq!(
         result.done,
-        "x new task 1 due:2020-02-02 t:2020-01-01 rec:+1y\n"
+        "x new task 1 due:2098-02-02 t:2098-01-01 rec:+1y\n"
     );
     assert_eq!(
         result.todo,
-        "new task 1 due:2021-02-02 t:2021-01-01 rec:+1y\n"
+        "\nnew task 1 due:2099-02-02 t:2099-01-01 rec:+1y\n"
     );
 }
 
+#[test]
+#[cfg(feature = "extended")]
+fn recurrence_every() {
+    let today = chrono::Local::now().date_naive();
+
+    let tasks = [
+        (
+            "a due:2099-01-05 every:mon,wed,fri",
+            "a due:2099-01-07 every:mon,wed,fri\n".to_string(),
+        ),
+        (
+            "b due:2099-01-13 t:2099-01-12 every:2nd-tue",
+            "b due:2099-02-10 t:2099-02-09 every:2nd-tue\n".to_string(),
+        ),
+        (
+            "c due:2099-01-30 count:2 every:last-bday",
+            "c due:2099-02-27 count:1 every:last-bday\n".to_string(),
+        ),
+        ("d due:2099-02-27 count:1 every:last-bday", String::new()),
+        ("e due:2099-01-30 every:fri until:2099-02-01", String::new()),
+        (
+            "f due:2021-01-01 every:mon,tue,wed,thu,fri,sat,sun",
+            format!("f due:{today} every:mon,tue,wed,thu,fri,sat,sun\n"),
+        ),
+        (
+            "g due:2021-01-01 rec:+1d",
+            format!("g due:{today} rec:+1d\n"),
+        ),
+        ("h due:2020-01-01 rec:+0d", String::new()),
+    ];
+
+    for (task, successor) in tasks {
+        let Result { todo_dir, .. } = exec("add", &[task]);
+
+        let result = reexec(todo_dir, "-n", &["done", "1"]);
+        assert_eq!(result.todo, successor);
+    }
+}
+
+#[test]
+fn reopen() {
+    let task = "new task 1 t:2098-01-01 due:2098-02-02 rec:+1y";
+    let Result { todo_dir, .. } = exec("add", &[task]);
+    let Result { todo_dir, .. } = reexec(todo_dir, "done", &["1"]);
+
+    let result = reexec(todo_dir, "reopen", &["1"]);
+    assert_eq!(result.done, "");
+    assert_eq!(
+        result.todo,
+        "\n\nnew task 1 due:2098-02-02 t:2098-01-01 rec:+1y\n"
+    );
+    assert_eq!(
+        result.stdout,
+        "TODO: Removed recurring task 'new task 1 due:2099-02-02 t:2099-01-01 rec:+1y'.\n3 new task 1 due:2098-02-02 t:2098-01-01 rec:+1y\nTODO: 1 reopened.\n"
+    );
+
+    let Result { todo_dir, .. } = reexec(
+        result.todo_dir,
+        "addto",
+        &["done.txt", "x other task id:ab12"],
+    );
+    let result = reexec(todo_dir, "reopen", &["#ab12"]);
+    assert_eq!(result.done, "");
+    assert!(result.todo.ends_with("other task id:ab12\n"));
+
+    let failure = failure(&result.todo_dir, "reopen", &["#ab12"]);
+    assert_eq!(failure.stderr, "TODO: No task #ab12.\n");
+
+    let Result { todo_dir, .. } = reexec(
+        result.todo_dir,
+        "addto",
+        &["done.txt", "x 2020-01-02 recent task"],
+    );
+    std::fs::write(todo_dir.join("done-2019.txt"), "x 2019-05-05 old task\n").unwrap();
+
+    let result = reexec(todo_dir, "reopen", &["2"]);
+    assert_eq!(result.done, "x 2020-01-02 recent task\n");
+    assert!(result.todo.ends_with("old task\n"));
+    assert_eq!(
+        std::fs::read_to_string(result.todo_dir.join("done-2019.txt")).unwrap(),
+        ""
+    );
+
+    let failure = self::failure(&result.todo_dir, "reopen", &["3"]);
+    assert_eq!(failure.stderr, "TODO: No task 3.\n");
+}
+
+#[test]
+fn undone() {
+    let task = "x new task 1\nnew task 2";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "undone", &["1"]);
+    assert_eq!(result.todo, "new task 1\nnew task 2\n");
+    assert_eq!(result.stdout, "1 new task 1\nTODO: 1 marked as not done.\n");
+
+    let failure = failure(&result.todo_dir, "undone", &["2"]);
+    assert_eq!(failure.code, Some(2));
+    assert_eq!(failure.stderr, "TODO: 2 is not marked as done.\n");
+
+    let task =
+        "x water plants due:2098-01-01 rec:1d\nwater plants due:2098-01-02 rec:1d\nnew task 3";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "undone", &["1"]);
+    assert_eq!(
+        result.todo,
+        "water plants due:2098-01-01 rec:1d\n\nnew task 3\n"
+    );
+}
+
+#[test]
+fn snooze() {
+    let today = chrono::Local::now().date_naive();
+    let in_3_days = today + chrono::Days::new(3);
+    let in_6_days = today + chrono::Days::new(6);
+
+    let task = "new task 1\nnew task 2 t:2021-01-01\nnew task 3";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "snooze", &["1", "3d"]);
+    assert_eq!(
+        result.stdout,
+        format!("1 new task 1 t:{in_3_days}\nTODO: 1 snoozed until {in_3_days}.\n")
+    );
+
+    let Result { todo_dir, .. } = reexec(result.todo_dir, "snooze", &["1", "3d"]);
+    let result = reexec(todo_dir, "defer", &["3", "2099-01-01"]);
+    assert_eq!(
+        result.todo,
+        format!("new task 1 t:{in_6_days}\nnew task 2 t:2021-01-01\nnew task 3 t:2099-01-01\n")
+    );
+
+    let envs = HashMap::from([("TODOTXT_RELDATE", "false")]);
+    let result = reexec_env(result.todo_dir, "list", &["--snoozed"], envs);
+    assert_eq!(
+        result.stdout,
+        format!(
+            "1 new task 1 t:{in_6_days}\n3 new task 3 t:2099-01-01\n--\nTODO: 2 of 3 tasks show\n"
+        )
+    );
+
+    let result = reexec(result.todo_dir, "wake", &[]);
+    assert_eq!(result.stdout, "2 new task 2\nTODO: 2 woken up.\n");
+
+    let result = reexec(result.todo_dir, "wake", &["1", "3"]);
+    assert_eq!(result.todo, "new task 1\nnew task 2\nnew task 3\n");
+}
+
+#[test]
+fn escalate() {
+    let today = chrono::Local::now().date_naive();
+    let tomorrow = today + chrono::Days::new(1);
+    let in_2_days = today + chrono::Days::new(2);
+    let in_10_days = today + chrono::Days::new(10);
+
+    let task = format!(
+        "new task 1 due:{tomorrow}\n(C) new task 2 due:{in_2_days}\n(A) new task 3 due:{tomorrow}\nnew task 4 due:{in_10_days}"
+    );
+    let Result { todo_dir, .. } = exec("addm", &[&task]);
+
+    let result = reexec(todo_dir, "escalate", &[]);
+    assert_eq!(
+        result.stdout,
+        format!(
+            "1 (A) new task 1 due:{tomorrow}\nTODO: 1 escalated to (A).\n2 (B) new task 2 due:{in_2_days}\nTODO: 2 escalated from (C) to (B).\n"
+        )
+    );
+
+    let envs = HashMap::from([
+        ("NO_COLOR", "true"),
+        ("TODOTXT_AUTO_ESCALATE", "true"),
+        ("TODOTXT_ESCALATION", "14:D"),
+    ]);
+    let task = format!("new task 5 due:{in_10_days}");
+    let result = reexec_env(result.todo_dir, "add", &[&task], envs);
+    assert_eq!(
+        result.stdout,
+        format!(
+            "4 (D) new task 4 due:{in_10_days}\nTODO: 4 escalated to (D).\n5 (D) new task 5 due:{in_10_days}\nTODO: 5 escalated to (D).\n5 new task 5 due:{in_10_days}\n"
+        )
+    );
+}
+
+#[test]
+fn next() {
+    let today = chrono::Local::now().date_naive();
+
+    let task = format!(
+        "(C) new task 1\nnew task 2 due:{today}\n(A) new task 3 +work\n(A) new task 4 due:{today} dep:x1\nnew task 5 id:x1"
+    );
+    let Result { todo_dir, .. } = exec("addm", &[&task]);
+
+    let envs = HashMap::from([("NO_COLOR", "true"), ("TODOTXT_RELDATE", "false")]);
+    let result = reexec_env(todo_dir, "next", &[], envs);
+    assert_eq!(
+        result.stdout,
+        format!(
+            "  8.8 2 new task 2 due:{today}\n  8.0 5 new task 5\n  6.0 3 (A) new task 3 +work\n  5.5 1 (C) new task 1\n--\nTODO: 4 of 5 tasks show\n"
+        )
+    );
+
+    let envs = HashMap::from([("NO_COLOR", "true"), ("TODOTXT_URGENCY", "+work:3")]);
+    let result = reexec_env(result.todo_dir, "next", &["1"], envs);
+    assert_eq!(
+        result.stdout,
+        "  9.0 3 (A) new task 3 +work\n--\nTODO: 1 of 5 tasks show\n"
+    );
+
+    let result = reexec(result.todo_dir, "next", &["+work"]);
+    assert_eq!(
+        result.stdout,
+        "  6.0 3 (A) new task 3 +work\n--\nTODO: 1 of 5 tasks show\n"
+    );
+
+    let result = reexec(result.todo_dir, "next", &["1", "new"]);
+    assert_eq!(
+        result.stdout,
+        "  8.8 2 new task 2 due:today\n--\nTODO: 1 of 5 tasks show\n"
+    );
+}
+
+#[test]
+fn deps() {
+    let task = "new task 1 dep:b2\nnew task 2 id:b2 dep:c3\nnew task 3 id:c3\nnew task 4 blocks:b2\nnew task 5 id:e5 dep:f6\nnew task 6 id:f6 dep:e5";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "deps", &["1"]);
+    assert_eq!(
+        result.stdout,
+        "1 new task 1 dep:b2\n└─ 2 new task 2 dep:c3\n   ├─ 3 new task 3\n   └─ 4 new task 4 blocks:b2\n"
+    );
+
+    let result = reexec(result.todo_dir, "deps", &["5"]);
+    assert_eq!(
+        result.stdout,
+        "5 new task 5 dep:f6\n└─ 6 new task 6 dep:e5\n   └─ 5 new task 5 dep:f6 (cycle)\nTODO: Dependency cycle 5 -> 6 -> 5.\n"
+    );
+
+    let result = reexec(result.todo_dir, "list", &[]);
+    assert_eq!(
+        result.stdout,
+        "3 new task 3\n4 new task 4 blocks:b2\n--\nTODO: 2 of 6 tasks show\n"
+    );
+
+    let Result { todo_dir, .. } = reexec(result.todo_dir, "done", &["3"]);
+    let result = reexec(todo_dir, "done", &["4"]);
+    assert_eq!(
+        result.stdout,
+        "4 x new task 4 blocks:b2\nTODO: 4 marked as done.\nTODO: 2 unblocked.\n"
+    );
+}
+
+#[test]
+fn subtasks() {
+    let task = "plan trip id:p1\nbook flights parent:p1\nbook hotel parent:p1 id:h1\ncompare prices parent:h1\nbuy milk";
+    let Result { todo_dir, .. } = exec("addm", &[task]);
+
+    let result = reexec(todo_dir, "ls", &["--tree"]);
+    assert_eq!(
+        result.stdout,
+        "1 plan trip (0/2 done)\n  2 book flights parent:p1\n  3 book hotel parent:p1 (0/1 done)\n    4 compare prices parent:h1\n5 buy milk\n--\nTODO: 5 of 5 tasks show\n"
+    );
+
+    let Result { todo_dir, .. } = reexec(result.todo_dir, "done", &["2"]);
+    let result = reexec(todo_dir, "ls", &["--tree"]);
+    assert_eq!(
+        result.stdout,
+        "1 plan trip (1/2 done)\n  3 book hotel parent:p1 (0/1 done)\n    4 compare prices parent:h1\n5 buy milk\n--\nTODO: 4 of 5 tasks show\n"
+    );
+
+    let result = reexec(result.todo_dir, "done", &["3"]);
+    assert_eq!(
+        result.stdout,
+        "3 x book hotel id:h1 parent:p1\nTODO: 3 marked as done.\n"
+    );
+
+    let result = reexec(result.todo_dir, "archive", &[]);
+    assert_eq!(result.done, "");
+
+    let Result { todo_dir, .. } = reexec(result.todo_dir, "done", &["4"]);
+    let result = reexec(todo_dir, "done", &["1"]);
+    assert_eq!(result.todo, "\n\n\n\nbuy milk\n");
+    assert_eq!(
+        result.done,
+        "x plan trip id:p1\nx book flights parent:p1\nx book hotel id:h1 parent:p1\nx compare prices parent:h1\n"
+    );
+
+    let result = reexec(result.todo_dir, "archive", &[]);
+    assert_eq!(result.todo, "buy milk\n");
+}
+
 #[test]
 fn replace() {
     let task = "new task 1\nnew task 2";
@@ -459,6 +1388,40 @@ fn exec(command: &str, args: &[&str]) -> Result {
     reexec(create_dir(), command, args)
 }
 
+fn failure(todo_dir: &std::path::Path, command: &str, args: &[&str]) -> Failure {
+    let mut envs = HashMap::new();
+    envs.insert("NO_COLOR", "true");
+
+    let assert = self::command(todo_dir, command, args, envs)
+        .assert()
+        .failure();
+    let output = assert.get_output();
+
+    Failure {
+        code: output.status.code(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    }
+}
+
+fn command(
+    todo_dir: &std::path::Path,
+    name: &str,
+    args: &[&str],
+    envs: HashMap<&str, &str>,
+) -> assert_cmd::Command {
+    let mut command = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+
+    command
+        .arg(name)
+        .args(args)
+        .envs(envs)
+        .env("TODOTXT_FORCE", "true")
+        .env("TODO_DIR", todo_dir)
+        .env("TODO_ACTIONS_DIR", todo_dir);
+
+    command
+}
+
 fn create_dir() -> std::path::PathBuf {
     let todo_dir = mktemp::Temp::new_dir().unwrap().to_path_buf();
     std::fs::create_dir(&todo_dir).unwrap();
@@ -483,14 +1446,7 @@ fn reexec_env(
     let done_file = todo_dir.join("done.txt");
     let report_file = todo_dir.join("report.txt");
 
-    let assert = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
-        .unwrap()
-        .arg(command)
-        .args(args)
-        .envs(envs)
-        .env("TODOTXT_FORCE", "true")
-        .env("TODO_DIR", &todo_dir)
-        .env("TODO_ACTIONS_DIR", &todo_dir)
+    let assert = self::command(&todo_dir, command, args, envs)
         .assert()
         .success();
 